@@ -0,0 +1,306 @@
+//! Finding the binary being tested, see [`init()`].
+//!
+//! The metadata gathered by [`init()`] is kept in environment variables, so it's shared by all the tests of a process:
+//!
+//! * `SANDBOX_TARGET_DIR`: Cargo's target directory.
+//! * `SANDBOX_PKG_NAME`: the name of the binary that is tested by default (if there is one).
+//! * `SANDBOX_BINS`: the names of all the binaries in the workspace, separated by [`BINS_SEPARATOR`].
+
+use std::{
+    env::{self, consts::EXE_SUFFIX},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, bail, Result};
+use cargo_metadata::{Metadata, MetadataCommand, Package};
+
+use crate::{binary, build, Binary};
+
+/// Separates the names in `SANDBOX_BINS`, Cargo doesn't allow it in target names.
+const BINS_SEPARATOR: char = ',';
+
+/// Initializes a new sandbox testing environment. Note that **this doesn't initialize a project**, just creates some
+/// environment variables with metadata about your project.
+///
+/// The binary that gets tested is the only binary of your package (the one in the current directory, as Cargo does),
+/// or the one named like your package (or its `default-run`) if it has several. From the root of a virtual workspace,
+/// the only binary of all its members. If there isn't one, pick it with [`init_with()`] or
+/// [`Project::bin()`](crate::Project::bin).
+///
+/// The binary that gets executed is the one Cargo built for your integration tests (`CARGO_BIN_EXE_<name>`), so custom
+/// targets, profiles and target directories work. If Cargo doesn't say where it is, it's looked for in the target
+/// directory.
+///
+/// # Panics
+///
+/// This function may panic if it cannot get your project's metadata.
+pub fn init() {
+    let md = metadata();
+    let packages = match current_package(&md) {
+        Some(current) => vec![current],
+        None => md.workspace_packages(),
+    };
+    let default = default_bin(&packages);
+    store(&md, default.as_deref());
+    build::set_default(None);
+}
+
+/// Same as [`init()`], but testing the binary `bin` of the workspace member `package`, for packages with several
+/// binaries (or workspaces with several packages).
+///
+/// Like with [`init()`], the choice is process-wide: it applies to every test of the process, and the last call wins,
+/// so tests that run in parallel and pick different binaries this way can run each other's. To test several binaries
+/// from the same test crate, pick one for each project with [`Project::bin()`](crate::Project::bin) instead.
+///
+/// ## Example
+///
+/// ```no_run
+/// #[test]
+/// fn server() {
+///     cli_sandbox::init_with("my-app", "my-app-server");
+///     let proj = cli_sandbox::project().unwrap();
+///     proj.command(["--version"]).unwrap();
+/// }
+/// ```
+///
+/// # Panics
+///
+/// This function will panic if it cannot get your project's metadata, or if `package` doesn't have a binary named
+/// `bin` (listing the ones that are available).
+pub fn init_with(package: &str, bin: &str) {
+    let md = metadata();
+    let packages = md.workspace_packages();
+    let Some(found) = packages.iter().find(|p| p.name == package) else {
+        let names = packages.iter().map(|p| p.name.as_str()).collect::<Vec<_>>();
+        panic!(
+            "There's no package named `{package}` in the workspace, the available ones are: {}",
+            names.join(", ")
+        );
+    };
+    let bins = bins(&[found]);
+    if !bins.contains(&bin) {
+        panic!(
+            "The package `{package}` doesn't have a binary named `{bin}`, {}",
+            available(&bins)
+        );
+    }
+    store(&md, Some(bin));
+    build::set_default(None);
+}
+
+/// Same as [`init()`], but instead of running whatever Cargo built last, the binary being tested is built (with
+/// `cargo build`) the first time a test of this process needs it, with `features` and the profile and target chosen
+/// for the project (see [`Project::profile()`](crate::Project::profile)).
+///
+/// This way, tests never run a stale binary, or one built with other features. Each binary is only built once per
+/// process, and a compilation error makes the tests that need it fail with the compiler's messages. Like with a
+/// [`Binary`], it's built in its own directory, so it doesn't replace the binary Cargo built for your tests.
+///
+/// Like the metadata gathered by [`init()`], this is process-wide: it applies to all the tests of the process (unless
+/// they choose a [`Binary`], see [`Project::binary()`](crate::Project::binary)) until [`init()`] is called again, which
+/// goes back to running the binary Cargo built.
+///
+/// ## Example
+///
+/// ```no_run
+/// # use cli_sandbox::{project, WithStdout};
+/// #[test]
+/// fn tls() {
+///     cli_sandbox::init_build(["tls"]);
+///     let proj = project().unwrap();
+///     proj.command(["--version"]).unwrap().with_stdout("my-cli 1.0.0 (with TLS)\n");
+/// }
+/// ```
+///
+/// # Panics
+///
+/// This function may panic if it cannot get your project's metadata.
+pub fn init_build<I, S>(features: I)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    init();
+    build::set_default(Some(binary().features(features).clone()));
+}
+
+fn metadata() -> Metadata {
+    MetadataCommand::new()
+        .no_deps()
+        .exec()
+        .expect("Couldn't get Cargo Metadata")
+}
+
+/// The workspace member that contains the current directory (the innermost one, if they're nested).
+fn current_package(md: &Metadata) -> Option<&Package> {
+    let current_dir = env::current_dir().ok()?;
+    md.workspace_packages()
+        .into_iter()
+        .filter(|p| {
+            p.manifest_path
+                .parent()
+                .is_some_and(|dir| current_dir.starts_with(dir))
+        })
+        .max_by_key(|p| p.manifest_path.as_str().len())
+}
+
+/// The names of the binaries of `packages`.
+fn bins<'m>(packages: &[&'m Package]) -> Vec<&'m str> {
+    packages
+        .iter()
+        .flat_map(|p| &p.targets)
+        .filter(|t| t.kind.iter().any(|kind| kind == "bin"))
+        .map(|t| t.name.as_str())
+        .collect()
+}
+
+/// The binary to test when none is chosen: the only one, or the one Cargo would run for a package with several.
+fn default_bin(packages: &[&Package]) -> Option<String> {
+    let bins = bins(packages);
+    if let [bin] = bins[..] {
+        return Some(bin.to_owned());
+    }
+    let [package] = packages else {
+        return None;
+    };
+    package.default_run.clone().or_else(|| {
+        bins.contains(&package.name.as_str())
+            .then(|| package.name.clone())
+    })
+}
+
+fn store(md: &Metadata, default: Option<&str>) {
+    env::set_var("SANDBOX_TARGET_DIR", &md.target_directory);
+    let all = bins(&md.workspace_packages());
+    env::set_var("SANDBOX_BINS", all.join(&BINS_SEPARATOR.to_string()));
+    match default {
+        Some(bin) => env::set_var("SANDBOX_PKG_NAME", bin),
+        None => env::remove_var("SANDBOX_PKG_NAME"),
+    }
+}
+
+/// Checks that the workspace has a binary named `name`, if [`init()`] gathered the binaries.
+pub(crate) fn check(name: &str) -> Result<()> {
+    match all_bins() {
+        Some(all) if !all.iter().any(|bin| bin == name) => {
+            bail!("There's no binary named `{name}`, {}", available(&all))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// All the binaries in the workspace, gathered by [`init()`].
+fn all_bins() -> Option<Vec<String>> {
+    let all = env::var("SANDBOX_BINS").ok()?;
+    Some(
+        all.split(BINS_SEPARATOR)
+            .filter(|bin| !bin.is_empty())
+            .map(str::to_owned)
+            .collect(),
+    )
+}
+
+fn available<S: AsRef<str>>(bins: &[S]) -> String {
+    if bins.is_empty() {
+        "there aren't any binaries".to_owned()
+    } else {
+        let bins = bins.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        format!("the available ones are: {}", bins.join(", "))
+    }
+}
+
+/// Whether the profile used when none is chosen is `release`, if only the `release` feature is enabled (as older
+/// versions required). Otherwise it's `dev`, and the binary Cargo built for the tests can be used.
+const RELEASE_BY_DEFAULT: bool = cfg!(all(feature = "release", not(feature = "dev")));
+
+/// The profile used when none is chosen.
+const DEFAULT_PROFILE: &str = if RELEASE_BY_DEFAULT { "release" } else { "dev" };
+
+/// Gets the path of the binary being tested (`bin`, or the default one), built with `profile` for the `target` triple.
+///
+/// If it's built with some features (`binary`) or [`init_build()`] was called, the binary is built (see
+/// [`Build::artifact()`]). Otherwise, if neither a profile nor a target are chosen (and the default profile is `dev`),
+/// the path Cargo gives to integration tests (`CARGO_BIN_EXE_<name>`) is preferred, as it takes custom targets,
+/// profiles and target directories into account. Otherwise, it's guessed from the metadata gathered by [`init()`]
+/// (and `CARGO_BUILD_TARGET`, if no target is chosen).
+pub(crate) fn path(
+    bin: Option<&str>,
+    profile: Option<&str>,
+    target: Option<&str>,
+    binary: Option<&Binary>,
+) -> Result<PathBuf> {
+    let cargo_bin = profile.is_none() && target.is_none() && !RELEASE_BY_DEFAULT;
+    let name = match bin {
+        Some(bin) => bin.to_owned(),
+        None => match env::var("SANDBOX_PKG_NAME") {
+            Ok(name) => name,
+            // Without `init()`, the binary can still be found if Cargo built only one for the tests.
+            Err(_) if env::var_os("SANDBOX_TARGET_DIR").is_none() => {
+                return (cargo_bin && binary.is_none())
+                    .then(only_cargo_bin)
+                    .flatten()
+                    .ok_or_else(|| anyhow!("Couldn't find your binary, call `cli_sandbox::init()` first"));
+            }
+            Err(_) => bail!(
+                "There isn't a default binary to test, pick one with `cli_sandbox::init_with()` or `Project::bin()`: {}",
+                available(&all_bins().unwrap_or_default())
+            ),
+        },
+    };
+
+    let profile_name = profile.unwrap_or(DEFAULT_PROFILE).to_owned();
+    if let Some(binary) = binary.cloned().or_else(build::default) {
+        return binary
+            .build(name, profile_name, target.map(str::to_owned))
+            .artifact();
+    }
+    if cargo_bin {
+        if let Some(path) = env::var_os(format!("CARGO_BIN_EXE_{name}")) {
+            return Ok(path.into());
+        }
+    }
+    let target_dir = env::var("SANDBOX_TARGET_DIR").map_err(|_| {
+        anyhow!("Couldn't find the binary `{name}`, call `cli_sandbox::init()` first")
+    })?;
+    let mut path = PathBuf::from(target_dir);
+    // Cargo puts what it builds for an explicit `--target` in a subdirectory named after it.
+    let target = target
+        .map(str::to_owned)
+        .or_else(|| env::var("CARGO_BUILD_TARGET").ok());
+    let suffix = match &target {
+        Some(target) => {
+            path.push(target);
+            if target.contains("-windows") {
+                ".exe"
+            } else {
+                ""
+            }
+        }
+        None => EXE_SUFFIX,
+    };
+    path.push(profile_dir(profile.unwrap_or(DEFAULT_PROFILE)));
+    path.push(format!("{name}{suffix}"));
+    Ok(path)
+}
+
+/// The directory (inside the target directory) where Cargo puts what it builds with `profile`.
+fn profile_dir(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        // Custom profiles (and `release`) get a directory named after them.
+        custom => custom,
+    }
+}
+
+/// The binary Cargo built for the tests, if it built exactly one.
+fn only_cargo_bin() -> Option<PathBuf> {
+    let mut bins = env::vars_os()
+        .filter(|(key, _)| {
+            key.to_str()
+                .is_some_and(|key| key.starts_with("CARGO_BIN_EXE_"))
+        })
+        .map(|(_, path)| PathBuf::from(path));
+    let bin = bins.next()?;
+    bins.next().is_none().then_some(bin)
+}
@@ -0,0 +1,101 @@
+//! Updating expected files with the actual output instead of failing, enabled with `SANDBOX_BLESS=1`.
+//!
+//! Each change is reported as it's made, and (on Unix) all of them are listed again when the test process exits.
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+
+use crate::fixture;
+
+/// Everything blessed in this test process (what was done, and to which file), for the summary.
+static BLESSED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Checks if the user asked to update the expected files (`SANDBOX_BLESS=1` or `SANDBOX_BLESS=true`).
+pub(crate) fn enabled() -> bool {
+    matches!(env::var("SANDBOX_BLESS").as_deref(), Ok("1" | "true"))
+}
+
+/// Writes `contents` to `path` if it's missing or outdated, reporting it to the user.
+pub(crate) fn write<C: AsRef<[u8]>>(path: &Path, contents: C) -> Result<()> {
+    let contents = contents.as_ref();
+    let action = match (fixture::is_link(path), fs::read(path)) {
+        (false, Ok(old)) if old == contents => return Ok(()),
+        // The expected file was a link, replace it instead of writing through it.
+        (true, _) => {
+            fs::remove_file(path).with_context(|| format!("Couldn't remove {}", path.display()))?;
+            "updated"
+        }
+        (false, Ok(_)) => "updated",
+        (false, Err(_)) => "created",
+    };
+
+    create_parent(path)?;
+    fs::write(path, contents).with_context(|| format!("Couldn't bless {}", path.display()))?;
+    report(path, action);
+    Ok(())
+}
+
+/// Makes `path` a symbolic link with the same target as `actual` if it's missing or outdated, reporting it to the user.
+pub(crate) fn link(path: &Path, actual: &Path) -> Result<()> {
+    let target = fs::read_link(actual)?;
+    let action = match fs::read_link(path) {
+        Ok(old) if old == target => return Ok(()),
+        _ if fs::symlink_metadata(path).is_ok() => "updated",
+        _ => "created",
+    };
+
+    create_parent(path)?;
+    fixture::copy_symlink(actual, path)
+        .with_context(|| format!("Couldn't bless {}", path.display()))?;
+    report(path, action);
+    Ok(())
+}
+
+/// Removes `path`, as it isn't produced anymore, reporting it to the user.
+pub(crate) fn remove(path: &Path) -> Result<()> {
+    fs::remove_file(path).with_context(|| format!("Couldn't remove {}", path.display()))?;
+    report(path, "removed");
+    Ok(())
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Couldn't create directory {}", parent.display()))?;
+    }
+    Ok(())
+}
+
+fn report(path: &Path, action: &str) {
+    let change = format!("{action} {}", path.display());
+    // Written straight to stderr so that the test harness doesn't capture it, it should be visible even if the test passes.
+    let _written = writeln!(io::stderr(), "SANDBOX_BLESS: {change}");
+    let mut blessed = BLESSED.lock().unwrap_or_else(|e| e.into_inner());
+    #[cfg(unix)]
+    if blessed.is_empty() {
+        // SAFETY: `summary` is a valid function that can be called at any time, and it doesn't unwind.
+        unsafe {
+            libc::atexit(summary);
+        }
+    }
+    blessed.push(change);
+}
+
+/// Prints everything that was blessed by the test process, once all of its tests are done (when it exits).
+#[cfg(unix)]
+extern "C" fn summary() {
+    let blessed = BLESSED.lock().unwrap_or_else(|e| e.into_inner());
+    let _written = writeln!(
+        io::stderr(),
+        "SANDBOX_BLESS: {} file{} blessed:\n  {}",
+        blessed.len(),
+        if blessed.len() == 1 { "" } else { "s" },
+        blessed.join("\n  ")
+    );
+}
@@ -0,0 +1,250 @@
+//! Building the binary being tested on demand, see [`init_build()`](crate::init_build) and [`binary()`].
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use cargo_metadata::{diagnostic::DiagnosticLevel, Message, Metadata, MetadataCommand};
+
+/// The binary being tested, built with a specific set of features, created with [`binary()`]. Executing it from a
+/// project (see [`Project::binary()`](crate::Project::binary)) builds it first, once per process.
+///
+/// Each combination of features is built in its own directory inside the target directory (`cli-sandbox/<features>`),
+/// so they don't invalidate each other (nor what Cargo builds for your tests) and they're only rebuilt when the code
+/// changes.
+///
+/// ## Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # use cli_sandbox::{binary, project, WithStdout};
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// cli_sandbox::init();
+/// for features in [&[][..], &["tls"], &["tls", "http2"]] {
+///     let mut proj = project()?;
+///     proj.binary(binary().no_default_features().features(features));
+///     proj.command(["--version"])?.with_stdout_data("my-cli [..]\n");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Binary {
+    features: Vec<String>,
+    no_default_features: bool,
+}
+
+/// Creates a new [`Binary`], with the default features.
+pub fn binary() -> Binary {
+    Binary::default()
+}
+
+impl Binary {
+    /// Enables some features of your package (as `cargo build --features`), along with the ones enabled before.
+    pub fn features<I, S>(&mut self, features: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.features
+            .extend(features.into_iter().map(|f| f.as_ref().to_owned()));
+        self
+    }
+
+    /// Disables the default features of your package (as `cargo build --no-default-features`).
+    pub const fn no_default_features(&mut self) -> &mut Self {
+        self.no_default_features = true;
+        self
+    }
+
+    /// How to build the binary `bin` with these features.
+    pub(crate) fn build(&self, bin: String, profile: String, target: Option<String>) -> Build {
+        let mut features = self.features.clone();
+        features.sort();
+        features.dedup();
+
+        // A readable name for the directory where this combination is built, e.g. `no-default+tls`.
+        let mut dir = if self.no_default_features {
+            "no-default"
+        } else {
+            "default"
+        }
+        .to_owned();
+        let mut replaced = false;
+        for feature in &features {
+            dir.push('+');
+            dir.extend(feature.chars().map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    replaced = true;
+                    '_'
+                }
+            }));
+        }
+        // Replacing characters can make different features look the same (e.g. `a.b` and `a_b`), a hash of the real
+        // ones keeps them apart. It must not change between Rust versions, or the directories would be rebuilt.
+        if replaced {
+            let _written = write!(dir, "-{:016x}", fnv(&features));
+        }
+
+        Build {
+            bin,
+            profile,
+            target,
+            features,
+            no_default_features: self.no_default_features,
+            dir,
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash of some features, separated by `,` (which Cargo doesn't allow in their names).
+fn fnv(features: &[String]) -> u64 {
+    features
+        .join(",")
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+/// How a binary gets built.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Build {
+    pub(crate) bin: String,
+    pub(crate) profile: String,
+    pub(crate) target: Option<String>,
+    pub(crate) features: Vec<String>,
+    pub(crate) no_default_features: bool,
+    /// The directory (in `cli-sandbox`, inside the target directory) where it's built.
+    pub(crate) dir: String,
+}
+
+/// What was built in each target directory (see [`Build::dir`]) by this process, the path of each binary or the error
+/// that prevented it, so each binary is only built once.
+type Built = BTreeMap<Build, Result<PathBuf, String>>;
+
+/// Everything built by this process, with a lock for each target directory.
+static BUILT: Mutex<BTreeMap<String, Arc<Mutex<Built>>>> = Mutex::new(BTreeMap::new());
+
+/// How the binaries are built when a project doesn't choose it, set by [`init_build()`](crate::init_build) (and unset
+/// by [`init()`](crate::init)) for all the tests of the process.
+static DEFAULT: Mutex<Option<Binary>> = Mutex::new(None);
+
+/// Builds the binaries on demand, as `binary` when a project doesn't choose how, or stops doing it if it's `None`.
+pub(crate) fn set_default(binary: Option<Binary>) {
+    *DEFAULT.lock().unwrap_or_else(|e| e.into_inner()) = binary;
+}
+
+/// How the binaries are built when a project doesn't choose it, `None` if they aren't built on demand.
+pub(crate) fn default() -> Option<Binary> {
+    DEFAULT.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+impl Build {
+    /// Builds the binary (if this process didn't build it yet), returning its path.
+    pub(crate) fn artifact(&self) -> Result<PathBuf> {
+        let dir = Arc::clone(
+            BUILT
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(self.dir.clone())
+                .or_default(),
+        );
+        // The directory's lock is kept while building, so tests that need the same binary wait for it instead of
+        // building it again, while the ones built in other directories don't have to wait.
+        let mut built = dir.lock().unwrap_or_else(|e| e.into_inner());
+        let result = built
+            .entry(self.clone())
+            .or_insert_with(|| self.run().map_err(|e| format!("{e:#}")));
+        result.clone().map_err(|e| anyhow!(e))
+    }
+
+    /// Runs `cargo build`, finding the path of the binary in its JSON messages.
+    fn run(&self) -> Result<PathBuf> {
+        let md = MetadataCommand::new().no_deps().exec()?;
+        let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+        let mut command = Command::new(cargo);
+        command
+            .arg("build")
+            .arg("--manifest-path")
+            .arg(self.manifest_path(&md)?)
+            .args(["--bin", &self.bin])
+            .args(["--profile", &self.profile])
+            .arg("--message-format=json")
+            .stdin(Stdio::null());
+        if let Some(target) = &self.target {
+            command.args(["--target", target]);
+        }
+        if !self.features.is_empty() {
+            command.args(["--features", &self.features.join(",")]);
+        }
+        if self.no_default_features {
+            command.arg("--no-default-features");
+        }
+        command
+            .arg("--target-dir")
+            .arg(md.target_directory.join("cli-sandbox").join(&self.dir));
+
+        let output = command
+            .output()
+            .with_context(|| format!("Couldn't run `cargo build` for the binary `{}`", self.bin))?;
+        let mut executable = None;
+        let mut errors = String::new();
+        for message in Message::parse_stream(&output.stdout[..]) {
+            match message? {
+                Message::CompilerArtifact(artifact)
+                    if artifact.target.name == self.bin
+                        && artifact.target.kind.iter().any(|kind| kind == "bin") =>
+                {
+                    executable = artifact.executable;
+                }
+                Message::CompilerMessage(msg)
+                    if matches!(
+                        msg.message.level,
+                        DiagnosticLevel::Error | DiagnosticLevel::Ice
+                    ) =>
+                {
+                    errors.push_str(
+                        msg.message
+                            .rendered
+                            .as_deref()
+                            .unwrap_or(&msg.message.message),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        if !output.status.success() {
+            // Errors that don't come from the compiler (e.g. a missing feature) are only in Cargo's stderr.
+            if errors.is_empty() {
+                errors = String::from_utf8_lossy(&output.stderr).into_owned();
+            }
+            bail!("Couldn't build the binary `{}`:\n{errors}", self.bin);
+        }
+        executable
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow!("`cargo build` didn't build the binary `{}`", self.bin))
+    }
+
+    /// The manifest of the workspace member that has the binary.
+    fn manifest_path(&self, md: &Metadata) -> Result<PathBuf> {
+        md.workspace_packages()
+            .into_iter()
+            .find(|p| {
+                p.targets
+                    .iter()
+                    .any(|t| t.name == self.bin && t.kind.iter().any(|kind| kind == "bin"))
+            })
+            .map(|p| p.manifest_path.clone().into())
+            .ok_or_else(|| anyhow!("There's no binary named `{}` in the workspace", self.bin))
+    }
+}
@@ -0,0 +1,165 @@
+//! Running declarative test cases from TOML files, see [`cases()`].
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::{
+    data::{self, Substitutions},
+    describe_status, fixture, glob, Project,
+};
+
+/// A test case, as written in its TOML file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Case {
+    args: Vec<String>,
+    stdin: Option<String>,
+    env: BTreeMap<String, String>,
+    /// A directory (relative to the package's root) to copy into the project before running the command.
+    fixture: Option<PathBuf>,
+    /// Files to create in the project before running the command, with their contents.
+    files: BTreeMap<String, String>,
+    /// The expected exit code.
+    code: Option<i32>,
+    stdout: Option<String>,
+    stderr: Option<String>,
+    /// Timeout in seconds.
+    timeout: Option<f64>,
+}
+
+/// Runs every test case described by the TOML files matching `pattern` (e.g. `"tests/cases/*.toml"`, relative to
+/// your package's root, `CARGO_MANIFEST_DIR`), each one in its own [`Project`].
+///
+/// All the cases are executed, and then every failure is reported at once. Remember to call [`init()`](crate::init)
+/// before, as the cases execute your binary the same way [`Project::command()`] does.
+///
+/// A case file can contain the following keys, all of them optional:
+///
+/// ```toml
+/// args = ["build", "main.py"]      # Arguments passed to your binary
+/// stdin = "some input"             # Standard input of your binary (end of input if not present)
+/// code = 0                         # Expected exit code (defaults to 0)
+/// timeout = 10                     # Seconds before your binary is killed
+/// fixture = "tests/fixtures/app"  # Directory copied into the project first (relative to your package's root)
+/// # Expected output, using the same wildcards as `WithStdout::with_stdout_data` ([..], ..., [ROOT], [EXE]).
+/// # If they aren't present, they aren't checked.
+/// stdout = "Compiled [ROOT]/main.py in [..]s"
+/// stderr = ""
+///
+/// [env]                            # Environment variables for your binary
+/// RUST_LOG = "debug"
+///
+/// [files]                          # Files to create in the project before running your binary (after the fixture)
+/// "main.py" = "print('Hello')"
+/// ```
+///
+/// ## Example
+///
+/// ```no_run
+/// #[test]
+/// fn cli_cases() {
+///     cli_sandbox::init();
+///     cli_sandbox::cases("tests/cases/*.toml");
+/// }
+/// ```
+///
+/// # Panics
+///
+/// This function will panic if any of the cases fails (including cases that can't be read or executed), or if no
+/// file matches `pattern`.
+pub fn cases(pattern: &str) {
+    let root = fixture::manifest_dir();
+    let paths = match glob::find(&root, pattern) {
+        Ok(paths) => paths,
+        Err(e) => panic!("Couldn't look for cases matching `{pattern}`: {e:#}"),
+    };
+    if paths.is_empty() {
+        panic!("No cases match `{pattern}`");
+    }
+
+    let failures = paths
+        .iter()
+        .filter_map(|path| {
+            run(path)
+                .unwrap_or_else(|e| Some(format!("{e:#}")))
+                .map(|failure| {
+                    let name = path.strip_prefix(&root).unwrap_or(path);
+                    format!("--- {}\n{failure}", name.display())
+                })
+        })
+        .collect::<Vec<_>>();
+
+    if !failures.is_empty() {
+        panic!(
+            "{} of {} cases failed:\n\n{}",
+            failures.len(),
+            paths.len(),
+            failures.join("\n\n")
+        );
+    }
+}
+
+/// Runs a single case, returning why it failed (if it did).
+fn run(path: &Path) -> Result<Option<String>> {
+    let case = fs::read_to_string(path).context("Couldn't read the case")?;
+    let case: Case = toml::from_str(&case).context("Couldn't parse the case")?;
+
+    let mut proj = match &case.fixture {
+        Some(fixture) => Project::from_fixture(fixture)?,
+        None => Project::new()?,
+    };
+    proj.new_files(&case.files)?;
+    if let Some(timeout) = case.timeout {
+        proj.timeout(Duration::try_from_secs_f64(timeout).context("Invalid timeout")?);
+    }
+
+    let mut cmd = proj.cmd();
+    cmd.args(&case.args);
+    for (key, val) in &case.env {
+        cmd.env(key, val);
+    }
+    if let Some(stdin) = &case.stdin {
+        cmd.stdin(stdin.as_str());
+    }
+    let result = cmd.run()?;
+
+    let mut problems = Vec::new();
+    let expected_code = case.code.unwrap_or(0);
+    if result.status.code() != Some(expected_code) {
+        problems.push(format!(
+            "Expected exit code {expected_code}, but it {}",
+            describe_status(result.status)
+        ));
+    }
+
+    let substitutions = Substitutions {
+        root: Some(proj.path()),
+        redactions: &[],
+    };
+    for (what, expected, actual) in [
+        ("stdout", &case.stdout, &result.stdout),
+        ("stderr", &case.stderr, &result.stderr),
+    ] {
+        let Some(expected) = expected else {
+            continue;
+        };
+        if let Err((expected, actual)) =
+            substitutions.compare(expected, &String::from_utf8_lossy(actual))
+        {
+            problems.push(format!(
+                "Unexpected {what}:\n{}",
+                data::diff(&expected, &actual)
+            ));
+        }
+    }
+
+    Ok((!problems.is_empty())
+        .then(|| format!("Running `{}`\n{}", result.invocation(), problems.join("\n"))))
+}
@@ -0,0 +1,623 @@
+use std::{
+    error::Error,
+    ffi::{OsStr, OsString},
+    fmt,
+    fs::File,
+    io::{self, Read, Write},
+    ops::Deref,
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+
+use anyhow::{anyhow, Context, Result};
+
+#[cfg(target_os = "linux")]
+use crate::PtySession;
+use crate::{bin, data::Redaction, Project, Session};
+
+/// How often a running program is checked to see if it has already finished.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A builder for running your binary (or any other program) inside of a [`Project`], created with [`Project::cmd()`]
+/// (or [`Project::program()`]).
+///
+/// It mirrors [`std::process::Command`], but the program is your binary (resolved the same way as
+/// [`Project::command()`]) unless another one is given, and relative paths are resolved against the project's directory.
+///
+/// ## Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # use cli_sandbox::{project, WithStdout};
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let proj = project()?;
+/// let cmd = proj
+///     .cmd()
+///     .args(["greet", "--loud"])
+///     .env("NAME", "Ferris")
+///     .stdin("some input")
+///     .run()?;
+/// cmd.with_stdout("HELLO FERRIS!");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct SandboxCommand<'p> {
+    project: &'p Project,
+    /// The program to execute, your binary if it's `None`.
+    program: Option<OsString>,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, Option<OsString>)>,
+    env_clear: bool,
+    current_dir: Option<PathBuf>,
+    stdin: Stdin,
+    timeout: Option<Duration>,
+    /// The profile your binary was built with, the project's one if it's `None`.
+    profile: Option<String>,
+    /// The target triple your binary was built for, the project's one if it's `None`.
+    target: Option<String>,
+}
+
+impl<'p> SandboxCommand<'p> {
+    pub(crate) const fn new(project: &'p Project, program: Option<OsString>) -> Self {
+        Self {
+            project,
+            program,
+            args: Vec::new(),
+            envs: Vec::new(),
+            env_clear: false,
+            current_dir: None,
+            stdin: Stdin::Null,
+            timeout: project.timeout,
+            profile: None,
+            target: None,
+        }
+    }
+
+    /// Adds an argument to pass to the program.
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    /// Adds multiple arguments to pass to the program.
+    pub fn args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    /// Sets an environment variable for the program.
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, key: K, val: V) -> &mut Self {
+        self.envs
+            .push((key.as_ref().to_owned(), Some(val.as_ref().to_owned())));
+        self
+    }
+
+    /// Removes an environment variable from the program's environment.
+    pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Self {
+        self.envs.push((key.as_ref().to_owned(), None));
+        self
+    }
+
+    /// Clears the whole environment of the program (including the project's one, see [`Project::env()`]), variables set
+    /// afterwards with [`SandboxCommand::env()`] are still passed.
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.envs.clear();
+        self.env_clear = true;
+        self
+    }
+
+    /// Sets the working directory of the program. A relative `dir` gets redirected to the project's real path (temporary and unknown).
+    pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.current_dir = Some(self.project.path().join(dir));
+        self
+    }
+
+    /// Sets the program's standard input: some text or bytes (e.g. `"some input"` or `b"\x00\x01"`), a file of the
+    /// project ([`Stdin::File`]) or nothing at all ([`Stdin::Null`]). If it isn't set, the program's stdin is connected to
+    /// the null device (reads as end of input).
+    pub fn stdin<S: Into<Stdin>>(&mut self, stdin: S) -> &mut Self {
+        self.stdin = stdin.into();
+        self
+    }
+
+    /// Sets how long the program can run before being killed, overriding the project's timeout (see [`Project::timeout()`]).
+    ///
+    /// If the time runs out, the program (and every process it spawned in its process group, on Unix) gets killed and
+    /// [`SandboxCommand::run()`] returns a [`TimeoutError`].
+    pub const fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the Cargo profile (`dev`, `release` or a custom one) your binary was built with, overriding the project's
+    /// one (see [`Project::profile()`]). It doesn't affect other programs (see [`Project::program()`]).
+    pub fn profile<S: Into<String>>(&mut self, profile: S) -> &mut Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Sets the target triple (e.g. `x86_64-unknown-linux-musl`) your binary was built for with `cargo build --target`,
+    /// overriding the project's one (see [`Project::target()`]).
+    pub fn target<S: Into<String>>(&mut self, target: S) -> &mut Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Executes the program, waiting for it to finish and collecting all of its output.
+    ///
+    /// ## Errors
+    ///
+    /// Apart from the program not being able to start, this returns a [`TimeoutError`] if the program runs for longer than its timeout.
+    pub fn run(&self) -> Result<CommandResult> {
+        let (program, current_dir, mut cmd) = self.command()?;
+        cmd.stdin(match &self.stdin {
+            Stdin::Null => Stdio::null(),
+            Stdin::Bytes(_) => Stdio::piped(),
+            Stdin::File(path) => {
+                let path = self.project.path().join(path);
+                File::open(&path)
+                    .with_context(|| format!("Couldn't open {} for stdin", path.display()))?
+                    .into()
+            }
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+        // Put the program in its own process group, so we can kill everything it spawned if it times out.
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        let start = Instant::now();
+        let mut child = cmd.spawn().with_context(|| self.spawn_error(&program))?;
+
+        let writer = match (&self.stdin, child.stdin.take()) {
+            // Write from another thread, so a program that doesn't read its whole stdin before writing can't deadlock us.
+            // The program may exit without reading all of its input (broken pipe), that's not an error.
+            (Stdin::Bytes(input), Some(mut pipe)) => {
+                let input = input.clone();
+                Some(thread::spawn(move || pipe.write_all(&input)))
+            }
+            _ => None,
+        };
+        let stdout = capture(child.stdout.take());
+        let stderr = capture(child.stderr.take());
+
+        // The output is only complete once the pipes are closed, which may happen after the program exits (e.g. if it
+        // left something running in the background), so the timeout covers that too.
+        // The program isn't reaped until its output is complete, so its process group can still be killed safely.
+        let mut exited = false;
+        let status = loop {
+            if !exited {
+                exited = match has_exited(&mut child) {
+                    Ok(exited) => exited,
+                    Err(e) => {
+                        kill(&mut child);
+                        return Err(e.into());
+                    }
+                };
+            }
+            if exited && stdout.is_done() && stderr.is_done() {
+                break child.wait()?;
+            }
+            if let Some(timeout) = self.timeout {
+                if start.elapsed() >= timeout {
+                    kill(&mut child);
+                    let elapsed = start.elapsed();
+                    // Give the readers a moment to drain whatever was left in the pipes.
+                    thread::sleep(POLL_INTERVAL);
+                    return Err(TimeoutError {
+                        command: self.invocation(&program),
+                        elapsed,
+                        stdout: stdout.snapshot(),
+                        stderr: stderr.snapshot(),
+                    }
+                    .into());
+                }
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+
+        if writer.is_some_and(|w| w.join().is_err()) {
+            return Err(anyhow!("The thread writing to stdin panicked"));
+        }
+        let output = Output {
+            status,
+            stdout: stdout.finish()?,
+            stderr: stderr.finish()?,
+        };
+        Ok(CommandResult {
+            program,
+            args: self.args.clone(),
+            envs: self.env_overrides(),
+            env_clear: self.env_clear,
+            root: self.project.path().to_owned(),
+            redactions: self.project.redactions.clone(),
+            current_dir,
+            duration: start.elapsed(),
+            output,
+        })
+    }
+}
+
+impl SandboxCommand<'_> {
+    /// Spawns the program attached to a new pseudo-terminal (24 rows and 80 columns), to interact with it as a user would
+    /// (see [`PtySession`]). `TERM` is set to `xterm-256color`, unless it's set (or removed) with [`SandboxCommand::env()`].
+    ///
+    /// The input set with [`SandboxCommand::stdin()`] is ignored, use [`PtySession::send()`] instead.
+    #[cfg(target_os = "linux")]
+    pub fn spawn_pty(&self) -> Result<PtySession> {
+        PtySession::spawn(self)
+    }
+
+    /// Spawns the program with its stdin and stdout connected to pipes, to interact with it as a script would (see
+    /// [`Session`]).
+    ///
+    /// The input set with [`SandboxCommand::stdin()`] is ignored, use [`Session::send()`] instead.
+    pub fn spawn(&self) -> Result<Session> {
+        Session::spawn(self)
+    }
+
+    /// Builds the [`Command`] to execute (without setting up its stdio), returning the program and the directory it's executed in too.
+    pub(crate) fn command(&self) -> Result<(PathBuf, PathBuf, Command)> {
+        let program = match &self.program {
+            Some(program) => PathBuf::from(program),
+            None => bin::path(
+                self.project.bin.as_deref(),
+                self.profile.as_deref().or(self.project.profile.as_deref()),
+                self.target.as_deref().or(self.project.target.as_deref()),
+                self.project.binary.as_ref(),
+            )?,
+        };
+        let current_dir = self
+            .current_dir
+            .clone()
+            .unwrap_or_else(|| self.project.path().to_owned());
+        let mut cmd = Command::new(&program);
+        cmd.current_dir(&current_dir).args(&self.args);
+        // The program never inherits the environment of the tests, they could affect each other through it.
+        cmd.env_clear();
+        if !self.env_clear {
+            cmd.envs(&self.project.envs);
+        }
+        for (key, val) in &self.envs {
+            match val {
+                Some(val) => cmd.env(key, val),
+                None => cmd.env_remove(key),
+            };
+        }
+        Ok((program, current_dir, cmd))
+    }
+
+    /// The context of the error returned when `program` can't be executed.
+    pub(crate) fn spawn_error(&self, program: &Path) -> String {
+        let mut error = format!("Couldn't execute {}", program.display());
+        if self.program.is_none() {
+            error.push_str(" (your binary may have to be built with the profile and target being tested first)");
+        }
+        error
+    }
+
+    /// The command line being executed, for error messages.
+    pub(crate) fn invocation(&self, program: &Path) -> String {
+        invocation(program, &self.args, &self.env_overrides())
+    }
+
+    /// The variables set (or removed) by the project and by this command, on top of the inherited ones (or nothing at
+    /// all, if the environment was cleared).
+    fn env_overrides(&self) -> Vec<(OsString, Option<OsString>)> {
+        let mut envs = Vec::new();
+        if !self.env_clear {
+            envs.extend(self.project.env_overrides());
+        }
+        envs.extend(self.envs.iter().cloned());
+        envs
+    }
+
+    /// The timeout of each expectation in an interactive session.
+    pub(crate) fn expect_timeout(&self) -> Duration {
+        self.timeout.unwrap_or(crate::expect::DEFAULT_TIMEOUT)
+    }
+
+    /// Whether the program's environment (or the project's one) sets (or removes) `key`.
+    pub(crate) fn sets_env(&self, key: &str) -> bool {
+        self.envs.iter().any(|(k, _)| k == key)
+            || (!self.env_clear && self.project.envs.contains_key(OsStr::new(key)))
+    }
+}
+
+/// The standard input of a program, see [`SandboxCommand::stdin()`].
+///
+/// Text and bytes convert into it, so `cmd.stdin("some input")` works as expected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Stdin {
+    /// No input at all, the program's stdin is connected to the null device (reads as end of input).
+    #[default]
+    Null,
+    /// Some text or bytes. They're written from another thread, so a program that writes a lot of output before
+    /// reading all of its input can't deadlock.
+    Bytes(Vec<u8>),
+    /// A file, relative to the project's directory.
+    File(PathBuf),
+}
+
+impl Stdin {
+    /// The contents of a file, relative to the project's directory.
+    pub fn file<P: AsRef<Path>>(path: P) -> Self {
+        Self::File(path.as_ref().to_owned())
+    }
+}
+
+impl From<Vec<u8>> for Stdin {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for Stdin {
+    fn from(bytes: &[u8]) -> Self {
+        Self::Bytes(bytes.to_vec())
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for Stdin {
+    fn from(bytes: &[u8; N]) -> Self {
+        Self::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<String> for Stdin {
+    fn from(text: String) -> Self {
+        Self::Bytes(text.into_bytes())
+    }
+}
+
+impl From<&str> for Stdin {
+    fn from(text: &str) -> Self {
+        Self::Bytes(text.as_bytes().to_vec())
+    }
+}
+
+/// The result of running a command: its [`Output`] along with how it was executed.
+///
+/// All the assertions from [`WithStdout`](crate::WithStdout) are available, and their failure messages include the exact
+/// command that was executed. It also dereferences to [`Output`], so `result.status`, `result.stdout`, etc. work as usual.
+#[derive(Debug, Clone)]
+pub struct CommandResult {
+    program: PathBuf,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, Option<OsString>)>,
+    env_clear: bool,
+    root: PathBuf,
+    pub(crate) redactions: Vec<Redaction>,
+    current_dir: PathBuf,
+    duration: Duration,
+    output: Output,
+}
+
+impl CommandResult {
+    /// The path of the program that was executed.
+    pub fn program(&self) -> &Path {
+        &self.program
+    }
+
+    /// The arguments that were passed to the program.
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// The environment variables that were set (`Some`) or removed (`None`) for the program, both by the project (see
+    /// [`Project::env()`]) and by the command itself. The program doesn't inherit your tests' environment, so these apply
+    /// on top of the variables in [`INHERITED_ENV`](crate::INHERITED_ENV), or on top of nothing at all if
+    /// [`CommandResult::env_cleared()`].
+    pub fn envs(&self) -> &[(OsString, Option<OsString>)] {
+        &self.envs
+    }
+
+    /// Whether the environment was cleared with [`SandboxCommand::env_clear()`] (dropping even the inherited variables)
+    /// before setting [`CommandResult::envs()`].
+    pub const fn env_cleared(&self) -> bool {
+        self.env_clear
+    }
+
+    /// The directory of the project the program was executed in (see [`Project::path()`]).
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The directory the program was executed in.
+    pub fn current_dir(&self) -> &Path {
+        &self.current_dir
+    }
+
+    /// How long the program took to run.
+    pub const fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// The output of the program.
+    pub const fn output(&self) -> &Output {
+        &self.output
+    }
+
+    /// Consumes the result, returning the output of the program.
+    pub fn into_output(self) -> Output {
+        self.output
+    }
+
+    /// Formats the command that was executed as it would be typed in a shell (roughly), including the environment variables
+    /// that were changed. It always starts with `env -i`, as the program never inherits your tests' environment (apart
+    /// from the variables in [`INHERITED_ENV`](crate::INHERITED_ENV)).
+    pub fn invocation(&self) -> String {
+        format!(
+            "{} (in {})",
+            invocation(&self.program, &self.args, &self.envs),
+            self.current_dir.display()
+        )
+    }
+}
+
+impl Deref for CommandResult {
+    type Target = Output;
+
+    fn deref(&self) -> &Output {
+        &self.output
+    }
+}
+
+/// The error returned when a program runs for longer than its timeout (see [`SandboxCommand::timeout()`] and [`Project::timeout()`]).
+///
+/// It contains all the output that the program wrote before being killed.
+#[derive(Debug, Clone)]
+pub struct TimeoutError {
+    /// The command line that was executed.
+    pub command: String,
+    /// How long the program ran before being killed.
+    pub elapsed: Duration,
+    /// The standard output captured before the program was killed.
+    pub stdout: Vec<u8>,
+    /// The standard error captured before the program was killed.
+    pub stderr: Vec<u8>,
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "`{}` timed out after {:.2}s and was killed",
+            self.command,
+            self.elapsed.as_secs_f64()
+        )?;
+        writeln!(f, "--- stdout")?;
+        writeln!(f, "{}", String::from_utf8_lossy(&self.stdout))?;
+        writeln!(f, "--- stderr")?;
+        write!(f, "{}", String::from_utf8_lossy(&self.stderr))
+    }
+}
+
+impl Error for TimeoutError {}
+
+/// Output of a program that is being read from another thread, so it can be looked at while the program is still running.
+#[derive(Debug)]
+pub(crate) struct Capture {
+    buf: Arc<Mutex<Vec<u8>>>,
+    reader: Option<thread::JoinHandle<()>>,
+}
+
+pub(crate) fn capture<R: Read + Send + 'static>(pipe: Option<R>) -> Capture {
+    let buf = Arc::new(Mutex::new(Vec::new()));
+    let reader = pipe.map(|mut pipe| {
+        let buf = Arc::clone(&buf);
+        thread::spawn(move || {
+            let mut chunk = [0; 4096];
+            while let Ok(n @ 1..) = pipe.read(&mut chunk) {
+                buf.lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .extend_from_slice(&chunk[..n]);
+            }
+        })
+    });
+    Capture { buf, reader }
+}
+
+impl Capture {
+    pub(crate) fn snapshot(&self) -> Vec<u8> {
+        self.buf.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Whether the pipe was closed and everything in it was read.
+    fn is_done(&self) -> bool {
+        self.reader
+            .as_ref()
+            .is_none_or(thread::JoinHandle::is_finished)
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>> {
+        if let Some(reader) = self.reader.take() {
+            reader
+                .join()
+                .map_err(|_| anyhow!("The thread reading the program's output panicked"))?;
+        }
+        Ok(self.snapshot())
+    }
+}
+
+/// Kills the program and, on Unix, every other process in its process group (even if the program already exited).
+///
+/// The program mustn't have been reaped yet (e.g. by [`Child::try_wait()`] returning its status), see [`has_exited()`].
+pub(crate) fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    if let Ok(pgid) = libc::pid_t::try_from(child.id()) {
+        // SAFETY: `kill` has no memory safety requirements, and the process group was created by us when spawning the
+        // child (`process_group(0)`, or `setsid` in PTY sessions, make the group id the same as the child's pid). The
+        // child wasn't reaped yet, so its pid can't be reused by another process (or process group) even if the group
+        // is already empty.
+        unsafe {
+            libc::kill(-pgid, libc::SIGKILL);
+        }
+    }
+    // The process may have already exited (or been killed above), so failing here is fine.
+    let _killed = child.kill();
+    let _status = child.wait();
+}
+
+/// Checks if the program exited. On Unix, it isn't reaped (that's left to [`Child::wait()`]), so its pid, which is the id
+/// of its process group too, can't be reused until then.
+fn has_exited(child: &mut Child) -> io::Result<bool> {
+    #[cfg(unix)]
+    {
+        // SAFETY: `siginfo_t` is plain data, for which all zeroes is a valid value.
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        // SAFETY: `info` is valid for writes for the duration of the call, and `WNOWAIT` leaves the child waitable, so
+        // `Child` can still reap it.
+        let result = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                child.id(),
+                &mut info,
+                libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+            )
+        };
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // Nothing is written to `info` if the child is still running.
+        Ok(info.si_signo != 0)
+    }
+    #[cfg(not(unix))]
+    child.try_wait().map(|status| status.is_some())
+}
+
+/// Formats a program, its arguments and its changed environment variables as they would be typed in a shell (roughly).
+/// The environment is always cleared first, as the program never inherits the tests' one.
+fn invocation(program: &Path, args: &[OsString], envs: &[(OsString, Option<OsString>)]) -> String {
+    let mut line = vec!["env".to_owned(), "-i".to_owned()];
+    line.extend(envs.iter().map(|(key, val)| match val {
+        Some(val) => format!("{}={}", key.to_string_lossy(), quote(val)),
+        None => format!("-u {}", key.to_string_lossy()),
+    }));
+    line.push(quote(program.as_os_str()));
+    line.extend(args.iter().map(|arg| quote(arg)));
+    line.join(" ")
+}
+
+fn quote(arg: &OsStr) -> String {
+    let arg = arg.to_string_lossy();
+    if arg.is_empty() || arg.contains(char::is_whitespace) {
+        format!("{arg:?}")
+    } else {
+        arg.into_owned()
+    }
+}
@@ -0,0 +1,205 @@
+//! Matching of output against expected data with wildcards and substitutions, in the style of
+//! [`cargo-test-support`](https://github.com/rust-lang/cargo/tree/master/crates/cargo-test-support).
+//!
+//! * `[..]` matches any text within a single line.
+//! * A line containing only `...` matches any number of lines (including none).
+//! * `[ROOT]` stands for the project's directory.
+//! * `[EXE]` stands for the platform's executable suffix (`.exe` on Windows, nothing anywhere else).
+//!
+//! Before comparing, the output is normalized: the project's directory becomes `[ROOT]`, the user's [`Redaction`]s are
+//! applied, and on Windows, path separators become `/`.
+
+use std::{env::consts::EXE_SUFFIX, path::Path};
+
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+/// A value in the output of a command that changes between runs (timestamps, PIDs, versions...), replaced by a stable
+/// token before comparing. Registered with [`Project::redact()`](crate::Project::redact) or
+/// [`Project::redact_regex()`](crate::Project::redact_regex).
+#[derive(Debug, Clone)]
+pub(crate) enum Redaction {
+    Literal {
+        token: String,
+        value: String,
+    },
+    #[cfg(feature = "regex")]
+    Regex {
+        token: String,
+        regex: Regex,
+    },
+}
+
+impl Redaction {
+    fn apply(&self, text: &str) -> String {
+        match self {
+            Self::Literal { token, value } if !value.is_empty() => text.replace(value, token),
+            Self::Literal { .. } => text.to_owned(),
+            #[cfg(feature = "regex")]
+            Self::Regex { token, regex } => {
+                regex.replace_all(text, regex::NoExpand(token)).into_owned()
+            }
+        }
+    }
+}
+
+/// Context used to turn the real output of a command into something that can be compared against expected data.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Substitutions<'a> {
+    /// The project's directory, replaced by `[ROOT]`.
+    pub(crate) root: Option<&'a Path>,
+    pub(crate) redactions: &'a [Redaction],
+}
+
+impl Substitutions<'_> {
+    /// Replaces the parts of `actual` that change between runs with their tokens (e.g. the project's path with `[ROOT]`).
+    pub(crate) fn normalize(&self, actual: &str) -> String {
+        let mut actual = actual.to_owned();
+        if let Some(root) = self.root {
+            // The canonical path is usually the longest (e.g. `/private/var/...` vs `/var/...` on macOS), replace it first.
+            if let Ok(canonical) = root.canonicalize() {
+                actual = actual.replace(&canonical.display().to_string(), "[ROOT]");
+            }
+            actual = actual.replace(&root.display().to_string(), "[ROOT]");
+        }
+        for redaction in self.redactions {
+            actual = redaction.apply(&actual);
+        }
+        if cfg!(windows) {
+            actual = actual.replace('\\', "/");
+        }
+        actual
+    }
+
+    /// Replaces the tokens in `expected` that stand for platform-specific values (e.g. `[EXE]`).
+    pub(crate) fn expand(expected: &str) -> String {
+        expected.replace("[EXE]", EXE_SUFFIX)
+    }
+
+    /// Checks that `actual` matches `expected`, returning both of them as they were compared if it doesn't.
+    pub(crate) fn compare(&self, expected: &str, actual: &str) -> Result<(), (String, String)> {
+        let expected = Self::expand(expected);
+        let actual = self.normalize(actual);
+        // All the trailing newlines are ignored, not only the last one (as `lines()` does).
+        let expected_lines = trim_newlines(&expected).lines().collect::<Vec<_>>();
+        let actual_lines = trim_newlines(&actual).lines().collect::<Vec<_>>();
+        if lines_match(&expected_lines, &actual_lines) {
+            Ok(())
+        } else {
+            Err((expected, actual))
+        }
+    }
+}
+
+fn trim_newlines(text: &str) -> &str {
+    text.trim_end_matches(['\r', '\n'])
+}
+
+/// Checks that all `actual` lines are matched by the `expected` lines, where `...` matches any number of lines.
+///
+/// It's iterative (outputs can have many lines): when a line doesn't match, the last `...` takes one more line and the
+/// lines after it are matched again from there.
+fn lines_match(expected: &[&str], actual: &[&str]) -> bool {
+    let (mut e, mut a) = (0, 0);
+    // The position of the last `...` in `expected`, and the line of `actual` the lines after it are matched from.
+    let mut backtrack = None;
+    loop {
+        if expected.get(e) == Some(&"...") {
+            backtrack = Some((e, a));
+            e += 1;
+        } else if e < expected.len() && a < actual.len() && line_matches(expected[e], actual[a]) {
+            e += 1;
+            a += 1;
+        } else if e == expected.len() && a == actual.len() {
+            return true;
+        } else if let Some((wildcard, from)) = backtrack.filter(|&(_, from)| from < actual.len()) {
+            backtrack = Some((wildcard, from + 1));
+            e = wildcard + 1;
+            a = from + 1;
+        } else {
+            return false;
+        }
+    }
+}
+
+/// Checks that an `actual` line is matched by an `expected` line, where `[..]` matches any text.
+pub(crate) fn line_matches(expected: &str, actual: &str) -> bool {
+    let mut parts = expected.split("[..]");
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = actual.strip_prefix(first) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Shows the differences between the expected and actual output, for failure messages that can't use `assert_eq!`.
+pub(crate) fn diff(expected: &str, actual: &str) -> String {
+    #[cfg(feature = "pretty_assertions")]
+    return pretty_assertions::StrComparison::new(expected, actual).to_string();
+    #[cfg(not(feature = "pretty_assertions"))]
+    return format!("--- expected\n{expected}\n--- actual\n{actual}");
+}
+
+/// How many bytes are shown in each line of a hex dump.
+const HEX_ROW: usize = 16;
+
+/// Shows where two binary contents differ: the first differing offset, and a hex dump of both around it.
+pub(crate) fn hex_diff(expected: &[u8], actual: &[u8]) -> String {
+    let offset = expected
+        .iter()
+        .zip(actual)
+        .position(|(expected, actual)| expected != actual)
+        .unwrap_or_else(|| expected.len().min(actual.len()));
+    // One row before the first difference, and two after it.
+    let start = (offset - offset % HEX_ROW).saturating_sub(HEX_ROW);
+    let end = start + 4 * HEX_ROW;
+    format!(
+        "First difference at offset {offset:#x} (expected {} bytes, got {} bytes)\n--- expected\n{}--- actual\n{}",
+        expected.len(),
+        actual.len(),
+        hex_dump(expected, start, end),
+        hex_dump(actual, start, end)
+    )
+}
+
+/// Dumps `bytes[start..end]` (or less, if there aren't enough) as hex, `xxd`-style.
+fn hex_dump(bytes: &[u8], start: usize, end: usize) -> String {
+    let bytes = &bytes[start.min(bytes.len())..end.min(bytes.len())];
+    if bytes.is_empty() {
+        return "(nothing)\n".to_owned();
+    }
+    bytes
+        .chunks(HEX_ROW)
+        .enumerate()
+        .map(|(i, row)| {
+            let hex = (0..HEX_ROW)
+                .map(|j| {
+                    row.get(j)
+                        .map_or_else(|| "  ".to_owned(), |b| format!("{b:02x}"))
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            let ascii = row
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+            format!("{:08x}  {hex}  |{ascii}|\n", start + i * HEX_ROW)
+        })
+        .collect()
+}
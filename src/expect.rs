@@ -0,0 +1,248 @@
+//! Waiting for some output of a program that is still running, and keeping a transcript of everything exchanged with it.
+
+use std::{
+    error::Error,
+    fmt,
+    io::Read,
+    process::{Child, ExitStatus},
+    str,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use crate::command::{kill, POLL_INTERVAL};
+
+/// How long an expectation waits when the command doesn't have a timeout.
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The output of a program, as it is being read from another thread.
+#[derive(Debug, Default)]
+struct Buffer {
+    data: Vec<u8>,
+    /// Whether the program closed its output.
+    eof: bool,
+}
+
+/// The output of a running program (read from another thread) along with the input sent to it.
+#[derive(Debug)]
+pub(crate) struct Exchange {
+    buffer: Arc<(Mutex<Buffer>, Condvar)>,
+    /// How much of the output was already consumed by previous expectations.
+    consumed: usize,
+    /// How much of the output is already in `transcript`.
+    logged: usize,
+    transcript: String,
+    /// How long each expectation waits, unless it chooses its own timeout.
+    pub(crate) timeout: Duration,
+}
+
+impl Exchange {
+    /// Starts reading `output` from another thread, until it ends (or fails, as a PTY does when the program exits).
+    pub(crate) fn new<R: Read + Send + 'static>(mut output: R, timeout: Duration) -> Self {
+        let buffer = Arc::new((Mutex::new(Buffer::default()), Condvar::new()));
+        let reader = Arc::clone(&buffer);
+        thread::spawn(move || {
+            let (buffer, condvar) = &*reader;
+            let mut chunk = [0; 4096];
+            loop {
+                let n = output.read(&mut chunk).unwrap_or(0);
+                let mut buffer = buffer.lock().unwrap_or_else(|e| e.into_inner());
+                buffer.data.extend_from_slice(&chunk[..n]);
+                buffer.eof = n == 0;
+                condvar.notify_all();
+                if n == 0 {
+                    break;
+                }
+            }
+        });
+        Self {
+            buffer,
+            consumed: 0,
+            logged: 0,
+            transcript: String::new(),
+            timeout,
+        }
+    }
+
+    /// Waits until `find` finds something in the output that wasn't consumed yet (returning where it ends), and consumes
+    /// the output up to there. Returns the consumed output.
+    ///
+    /// `expected` describes what is being waited for, for the error message.
+    pub(crate) fn expect<F>(
+        &mut self,
+        expected: &str,
+        timeout: Duration,
+        find: F,
+    ) -> Result<String, ExpectError>
+    where
+        F: Fn(&str, bool) -> Option<usize>,
+    {
+        let start = Instant::now();
+        let shared = Arc::clone(&self.buffer);
+        let (buffer, condvar) = &*shared;
+        let mut guard = buffer.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            let decoded = Decoded::new(&guard.data[self.consumed..], guard.eof);
+            if let Some(end) = find(&decoded.text, guard.eof) {
+                self.consumed += decoded.output_len(end);
+                return Ok(decoded.text[..end].to_owned());
+            }
+
+            let waited = start.elapsed();
+            if guard.eof || waited >= timeout {
+                let eof = guard.eof;
+                drop(guard);
+                return Err(ExpectError {
+                    expected: expected.to_owned(),
+                    waited,
+                    eof,
+                    transcript: self.transcript(),
+                });
+            }
+            guard = condvar
+                .wait_timeout(guard, timeout - waited)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Records some input that was sent to the program, after all the output received until now.
+    pub(crate) fn record_input(&mut self, input: &[u8]) {
+        self.log_output();
+        self.transcript.push_str("--- input\n");
+        self.transcript.push_str(&String::from_utf8_lossy(input));
+        if !input.ends_with(b"\n") {
+            self.transcript.push('\n');
+        }
+    }
+
+    /// Everything that was exchanged with the program until now.
+    pub(crate) fn transcript(&mut self) -> String {
+        self.log_output();
+        self.transcript.clone()
+    }
+
+    fn log_output(&mut self) {
+        let buffer = self.buffer.0.lock().unwrap_or_else(|e| e.into_inner());
+        let output = &buffer.data[self.logged..];
+        if !output.is_empty() {
+            self.transcript.push_str("--- output\n");
+            self.transcript.push_str(&String::from_utf8_lossy(output));
+            if !output.ends_with(b"\n") {
+                self.transcript.push('\n');
+            }
+        }
+        self.logged = buffer.data.len();
+    }
+
+    /// Waits for the program to exit. If it's still running after the timeout, it gets killed.
+    pub(crate) fn wait(&mut self, child: &mut Child) -> Result<ExitStatus> {
+        let start = Instant::now();
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status);
+            }
+            let waited = start.elapsed();
+            if waited >= self.timeout {
+                kill(child);
+                return Err(ExpectError {
+                    expected: "the program to exit".to_owned(),
+                    waited,
+                    eof: false,
+                    transcript: self.transcript(),
+                }
+                .into());
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Some output decoded as text, with invalid bytes replaced by `U+FFFD`.
+struct Decoded {
+    text: String,
+    /// Where each valid or invalid part of the output starts, in `text` and in the output, followed by where both end.
+    parts: Vec<(usize, usize)>,
+}
+
+impl Decoded {
+    /// Decodes `output`. An incomplete character at the end is left out unless the output ended, the rest of it may
+    /// not have been read yet.
+    fn new(output: &[u8], eof: bool) -> Self {
+        let mut text = String::new();
+        let mut parts = Vec::new();
+        let mut read = 0;
+        while read < output.len() {
+            let rest = &output[read..];
+            let (valid, invalid) = match str::from_utf8(rest) {
+                Ok(valid) => (valid, None),
+                Err(e) => (
+                    str::from_utf8(&rest[..e.valid_up_to()]).unwrap_or_default(),
+                    e.error_len()
+                        .or_else(|| eof.then(|| rest.len() - e.valid_up_to())),
+                ),
+            };
+            parts.push((text.len(), read));
+            text.push_str(valid);
+            read += valid.len();
+            let Some(len) = invalid else {
+                break;
+            };
+            parts.push((text.len(), read));
+            text.push(char::REPLACEMENT_CHARACTER);
+            read += len;
+        }
+        parts.push((text.len(), read));
+        Self { text, parts }
+    }
+
+    /// How much of the output the text up to `end` comes from.
+    fn output_len(&self, end: usize) -> usize {
+        self.parts
+            .iter()
+            .rev()
+            .find(|(text, _)| *text <= end)
+            .map_or(end, |(text, output)| output + (end - text))
+    }
+}
+
+/// The error returned when the output that was expected from an interactive program doesn't show up, either because
+/// it takes too long or because the program closes its output.
+///
+/// It contains a transcript of everything that was exchanged with the program until then.
+#[derive(Debug, Clone)]
+pub struct ExpectError {
+    /// What was expected.
+    pub expected: String,
+    /// How long it was waited for.
+    pub waited: Duration,
+    /// Whether the program closed its output (e.g. by exiting) before it showed up, instead of running out of time.
+    pub eof: bool,
+    /// All the output of the program and all the input sent to it, in order.
+    pub transcript: String,
+}
+
+impl fmt::Display for ExpectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.eof {
+            writeln!(
+                f,
+                "The program's output ended while waiting for {}",
+                self.expected
+            )?;
+        } else {
+            writeln!(
+                f,
+                "Timed out after {:.2}s waiting for {}",
+                self.waited.as_secs_f64(),
+                self.expected
+            )?;
+        }
+        write!(f, "{}", self.transcript)
+    }
+}
+
+impl Error for ExpectError {}
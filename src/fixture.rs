@@ -0,0 +1,66 @@
+//! Copying fixture directories into projects.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+/// The root of the package being tested, used to resolve the paths of fixtures.
+///
+/// Cargo sets `CARGO_MANIFEST_DIR` when running tests, if it isn't set (e.g. the test binary is executed directly),
+/// the current directory is used.
+pub(crate) fn manifest_dir() -> PathBuf {
+    env::var_os("CARGO_MANIFEST_DIR").map_or_else(|| PathBuf::from("."), PathBuf::from)
+}
+
+/// Recursively copies `src` into `dst`, keeping permissions and symbolic links (links aren't followed, they're copied as links).
+pub(crate) fn copy_dir(src: &Path, dst: &Path) -> Result<()> {
+    fs::create_dir_all(dst).with_context(|| format!("Couldn't create {}", dst.display()))?;
+    let entries =
+        fs::read_dir(src).with_context(|| format!("Couldn't read directory {}", src.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let (from, to) = (entry.path(), dst.join(entry.file_name()));
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            copy_symlink(&from, &to)?;
+        } else if file_type.is_dir() {
+            copy_dir(&from, &to)?;
+        } else {
+            // `fs::copy` would follow a link at `to`, overwriting its target (which may be outside of the project).
+            if is_link(&to) {
+                fs::remove_file(&to)
+                    .with_context(|| format!("Couldn't replace {}", to.display()))?;
+            }
+            fs::copy(&from, &to)
+                .with_context(|| format!("Couldn't copy {} to {}", from.display(), to.display()))?;
+        }
+    }
+    // Set the permissions last, the directory may be read-only.
+    fs::set_permissions(dst, fs::metadata(src)?.permissions())?;
+    Ok(())
+}
+
+/// Creates a symbolic link at `to` with the same target as the one at `from`, replacing the file or link at `to`.
+pub(crate) fn copy_symlink(from: &Path, to: &Path) -> Result<()> {
+    let target = fs::read_link(from)?;
+    if is_link(to) || to.is_file() {
+        fs::remove_file(to).with_context(|| format!("Couldn't replace {}", to.display()))?;
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&target, to)?;
+    #[cfg(windows)]
+    if from.is_dir() {
+        std::os::windows::fs::symlink_dir(&target, to)?;
+    } else {
+        std::os::windows::fs::symlink_file(&target, to)?;
+    }
+    Ok(())
+}
+
+/// Checks if `path` is a symbolic link (without following it).
+pub(crate) fn is_link(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+}
@@ -0,0 +1,92 @@
+//! Minimal glob patterns for paths: `*` and `?` match within a path component, and `**` matches any number of components.
+
+#[cfg(feature = "cases")]
+use std::path::PathBuf;
+use std::{fs, path::Path};
+
+use anyhow::Result;
+
+/// Checks if `path` (using `/` as separator) matches `pattern`.
+pub(crate) fn matches(pattern: &str, path: &str) -> bool {
+    let pattern = components(pattern);
+    let path = components(path);
+    components_match(&pattern, &path)
+}
+
+/// Finds all the files matching `pattern`, relative to `root` if it isn't absolute. The result is sorted.
+#[cfg(feature = "cases")]
+pub(crate) fn find(root: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let pattern = pattern.replace('\\', "/");
+    let all = components(&pattern);
+    // Only walk the directory that contains all the matches, instead of the whole `root`.
+    let literal = all.iter().take_while(|c| !c.contains(['*', '?'])).count();
+    let mut base = if pattern.starts_with('/') {
+        PathBuf::from("/")
+    } else {
+        root.to_owned()
+    };
+    base.extend(&all[..literal]);
+    let rest = all[literal..].join("/");
+
+    let mut found = Vec::new();
+    if rest.is_empty() {
+        if base.is_file() {
+            found.push(base);
+        }
+    } else if base.is_dir() {
+        walk(&base, &mut |path| {
+            let relative = path.strip_prefix(&base).unwrap_or(path);
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            if matches(&rest, &relative) {
+                found.push(path.to_owned());
+            }
+        })?;
+    }
+
+    found.sort();
+    Ok(found)
+}
+
+/// Calls `f` with every file inside `dir`, recursively. Symbolic links aren't followed, `f` is called with them too.
+pub(crate) fn walk(dir: &Path, f: &mut dyn FnMut(&Path)) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            walk(&path, f)?;
+        } else {
+            f(&path);
+        }
+    }
+    Ok(())
+}
+
+fn components(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect()
+}
+
+fn components_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| components_match(rest, &path[skip..])),
+        Some((component, rest)) => match path.split_first() {
+            Some((path_component, path_rest)) => {
+                let component = component.chars().collect::<Vec<_>>();
+                let path_component = path_component.chars().collect::<Vec<_>>();
+                component_matches(&component, &path_component) && components_match(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn component_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| component_matches(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && component_matches(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && component_matches(rest, &name[1..]),
+    }
+}
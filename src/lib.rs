@@ -20,7 +20,7 @@
 //! #[test]
 //! fn compiling() -> Result<(), Box<dyn Error>> {
 //!     cli_sandbox::init(); // Initialize the sandbox
-//!     let proj = project()?;                      // Create a project
+//!     let mut proj = project()?;                  // Create a project
 //!
 //!     // Let's create a file, and put in there some Python.
 //!     proj.new_file("my-program.py",
@@ -69,9 +69,18 @@
 //! ## Features
 //!
 //! * Regex support for checking `stdout` and `stderr`. (feature: `regex`)
+//! * `cargo-test-support`-like wildcards (`[..]`, `...`, `[ROOT]`, `[EXE]`) for checking `stdout` and `stderr` (`with_stdout_data`, `with_stderr_data`)
 //! * All output is beautiful thanks to [`pretty-assertions`](https://docs.rs/pretty_assertions/latest/pretty_assertions/) and [`better_panic`](https://docs.rs/better_panic). (feature: `pretty`, also can be enabled individually)
+//! * Running declarative test cases from TOML files with `cases("tests/cases/*.toml")` (feature: `cases`)
+//! * Checking that the `console` transcripts in your Markdown documentation match your binary's output (`check_markdown`)
+//! * Comparing whole directories against an expected tree (`Project::check_dir`)
+//! * Interactive sessions in a pseudo-terminal, for prompts, colors and TUIs (`SandboxCommand::spawn_pty`, Linux only)
+//! * Expect-style scripted sessions over pipes, for REPLs (`SandboxCommand::spawn`)
 //! * Little fuzzing functionality (feature: `fuzz`)
-//! * Testing either the `debug` or `release` profile (features: `dev` or `release`)
+//! * Testing any profile (`dev`, `release` or a custom one), chosen at runtime for each project or command (`Project::profile`)
+//! * Building the binary on demand, with the features you choose, so tests never run a stale one (`init_build`)
+//! * Testing several feature combinations of your binary in the same suite (`binary().features([..])`)
+//! * Isolated environment variables for each project, setting them doesn't change your tests' environment (`Project::env`)
 //!
 
 // All code blocks in fragments must be ignored because rustdoc hates environment variables, it seems.
@@ -99,7 +108,7 @@
     clippy::branches_sharing_code,
     clippy::cast_possible_wrap,
     clippy::doc_markdown,
-    clippy::empty_enum,
+    clippy::empty_enums,
     clippy::if_not_else,
     clippy::inefficient_to_string,
     clippy::items_after_statements,
@@ -121,16 +130,20 @@
     clippy::too_many_lines,
     clippy::unused_self
 )]
+// The crate example is a whole test, as you'd write it.
+#![allow(clippy::test_attr_in_doctest)]
 
 use std::{
+    collections::BTreeMap,
     env,
-    ffi::OsStr,
-    fs::{write, File},
+    ffi::{OsStr, OsString},
+    fs::{create_dir_all, write, File},
     io::Read,
     os,
     path::Path,
-    process::{Command, Output},
+    process::ExitStatus,
     str,
+    time::Duration,
 };
 
 use anyhow::Result;
@@ -141,6 +154,37 @@ use pretty_assertions::assert_eq;
 #[cfg(feature = "regex")]
 use regex::Regex;
 use tempfile::{tempdir, TempDir};
+
+mod bin;
+mod bless;
+mod build;
+#[cfg(feature = "cases")]
+mod cases;
+mod command;
+mod data;
+mod expect;
+mod fixture;
+mod glob;
+mod markdown;
+mod output;
+#[cfg(target_os = "linux")]
+mod pty;
+mod session;
+mod snapshot;
+mod tree;
+pub use bin::{init, init_build, init_with};
+pub use build::{binary, Binary};
+#[cfg(feature = "cases")]
+pub use cases::cases;
+pub use command::{CommandResult, SandboxCommand, Stdin, TimeoutError};
+use data::Redaction;
+pub use expect::ExpectError;
+pub use markdown::check_markdown;
+#[cfg(target_os = "linux")]
+pub use pty::{Key, PtySession};
+pub use session::Session;
+pub use snapshot::{Changes, Snapshot};
+
 #[cfg(feature = "better_panic")]
 pub mod panic {
     use better_panic::{Settings, Verbosity};
@@ -170,9 +214,47 @@ pub mod panic {
     }
 }
 
+/// The environment variables that the programs executed in a project inherit from your tests, see
+/// [`Project::env()`]. The rest of your tests' environment isn't passed to them.
+pub const INHERITED_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    // Windows programs may not even start without them, or not find the programs and folders they need.
+    "SYSTEMROOT",
+    "WINDIR",
+    "PATHEXT",
+    "COMSPEC",
+    "USERPROFILE",
+    "APPDATA",
+    // Cargo uses them to find the dynamic libraries your binary links to.
+    "LD_LIBRARY_PATH",
+    "DYLD_LIBRARY_PATH",
+    "DYLD_FALLBACK_LIBRARY_PATH",
+    // Coverage tools (e.g. `cargo llvm-cov`) use it to collect the coverage of your binary.
+    "LLVM_PROFILE_FILE",
+];
+
 #[derive(Debug)]
 pub struct Project {
     tempdir: TempDir,
+    /// The environment of the programs executed in this project, only the [`INHERITED_ENV`] of the tests by default.
+    envs: BTreeMap<OsString, OsString>,
+    /// The variables of [`INHERITED_ENV`] that were set when the project was created, to tell which ones were changed.
+    inherited_envs: BTreeMap<OsString, OsString>,
+    /// The binary executed by [`Project::command()`], the default one if it's `None`.
+    bin: Option<String>,
+    /// The profile the binary was built with, the one Cargo built for the tests if it's `None`.
+    profile: Option<String>,
+    /// The target triple the binary was built for, the host (or `CARGO_BUILD_TARGET`) if it's `None`.
+    target: Option<String>,
+    /// The features the binary is built with, if it isn't the one Cargo built for the tests.
+    binary: Option<Binary>,
+    timeout: Option<Duration>,
+    redactions: Vec<Redaction>,
 }
 
 /// Shortcut for [`Project::new()`].
@@ -181,88 +263,565 @@ pub fn project() -> Result<Project> {
     Project::new()
 }
 
-/// Initializes a new sandbox testing environment. Note that **this doesn't initialize a project**, just creates some
-/// environment variables with metadata about your project.
-///
-/// # Panics
-///
-/// This function may panic if it cannot find the root package metadata (a.k.a your project's metadata).
-pub fn init() {
-    let md = cargo_metadata::MetadataCommand::new()
-        .exec()
-        .expect("Couldn't get Cargo Metadata");
-
-    let root = md.root_package().unwrap();
-    env::set_var("SANDBOX_TARGET_DIR", &md.target_directory);
-    env::set_var("SANDBOX_PKG_NAME", &root.name);
-}
-
 impl Project {
     /// Creates a new [`Project`]
     ///
     pub fn new() -> Result<Self> {
+        let envs: BTreeMap<_, _> = INHERITED_ENV
+            .iter()
+            .filter_map(|key| Some((OsString::from(key), env::var_os(key)?)))
+            .collect();
         Ok(Self {
             tempdir: tempdir()?,
+            inherited_envs: envs.clone(),
+            envs,
+            bin: None,
+            profile: None,
+            target: None,
+            binary: None,
+            timeout: None,
+            redactions: Vec::new(),
         })
     }
 
+    /// Sets the binary executed by [`Project::command()`] and [`Project::cmd()`] in this project, for packages with
+    /// several binaries (or workspaces with several packages). By default, it's the one chosen by [`init()`].
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut proj = project()?;
+    /// proj.bin("my-app-client")?;
+    /// proj.command(["--version"])?.with_stdout_data("my-app-client [..]\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Errors
+    ///
+    /// This fails if the workspace doesn't have a binary named `name`, listing the ones that are available.
+    pub fn bin(&mut self, name: &str) -> Result<&mut Self> {
+        bin::check(name)?;
+        self.bin = Some(name.to_owned());
+        Ok(self)
+    }
+
+    /// Sets the Cargo profile your binary was built with (`dev`, `release` or a custom one, like `ci` for a
+    /// `[profile.ci]`) for the commands executed in this project. It can be overridden for a single command with
+    /// [`SandboxCommand::profile()`].
+    ///
+    /// By default, the binary Cargo built for your tests is used (or the one in the `dev` profile, if Cargo doesn't
+    /// say). With only the `release` feature enabled, the one in the `release` profile is used instead. Note that the
+    /// binary has to be built with that profile beforehand (e.g. `cargo build --release`).
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut proj = project()?;
+    /// proj.profile("release");
+    /// proj.command(["--version"])?.with_stdout_data("my-cli [..]\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn profile<S: Into<String>>(&mut self, profile: S) -> &mut Self {
+        self.profile = Some(profile.into());
+        self
+    }
+
+    /// Sets the target triple your binary was built for (with `cargo build --target <triple>`), for the commands executed
+    /// in this project. It can be overridden for a single command with [`SandboxCommand::target()`].
+    ///
+    /// By default, the binary Cargo built for your tests is used (or the one for `CARGO_BUILD_TARGET` or the host, if
+    /// Cargo doesn't say).
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut proj = project()?;
+    /// proj.profile("release").target("x86_64-unknown-linux-musl");
+    /// proj.command(["--version"])?.with_stdout_data("my-cli [..]\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn target<S: Into<String>>(&mut self, target: S) -> &mut Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Sets an environment variable for all the programs executed in this project.
+    ///
+    /// The programs don't inherit your tests' environment (so tests can't affect each other through it), only the
+    /// variables in [`INHERITED_ENV`], the ones set here and the ones set for each command (see
+    /// [`SandboxCommand::env()`]).
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut proj = project()?;
+    /// proj.env("MY_CLI_CONFIG", "config.toml").env_inherit("RUST_LOG");
+    /// proj.command(["check"])?.with_stdout("Everything is fine\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, key: K, val: V) -> &mut Self {
+        self.envs
+            .insert(key.as_ref().to_owned(), val.as_ref().to_owned());
+        self
+    }
+
+    /// Removes an environment variable from the environment of this project (e.g. one of [`INHERITED_ENV`]).
+    pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Self {
+        self.envs.remove(key.as_ref());
+        self
+    }
+
+    /// Passes an environment variable of your tests to the programs executed in this project, if it's set.
+    pub fn env_inherit<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Self {
+        let key = key.as_ref();
+        match env::var_os(key) {
+            Some(val) => self.env(key, val),
+            None => self.env_remove(key),
+        }
+    }
+
+    /// Executes the binary built with some features (see [`Binary`]) in this project, instead of the one Cargo built
+    /// for your tests.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{binary, project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut proj = project()?;
+    /// proj.binary(binary().features(["tls"]).no_default_features());
+    /// proj.command(["--version"])?.with_stdout("my-cli 1.0.0 (with TLS)\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn binary(&mut self, binary: &Binary) -> &mut Self {
+        self.binary = Some(binary.clone());
+        self
+    }
+
+    /// Replaces every occurrence of `value` with `token` in the output of the commands executed in this project,
+    /// before checking it with [`WithStdout`]. Useful for values that change between runs, or between machines.
+    ///
+    /// The project's path is always replaced with `[ROOT]`, and on Windows, `\` path separators are replaced with `/`.
+    /// Only the output is redacted, never the expected text, so it has to use the token instead of the value.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut proj = project()?;
+    /// proj.redact("[VERSION]", env!("CARGO_PKG_VERSION"));
+    /// let cmd = proj.command(["--version"])?;
+    /// cmd.with_stdout("my-cli [VERSION]\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn redact<T: Into<String>, V: Into<String>>(&mut self, token: T, value: V) -> &mut Self {
+        self.redactions.push(Redaction::Literal {
+            token: token.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    /// Replaces every match of `regex` with `token` in the output of the commands executed in this project, before
+    /// checking it with [`WithStdout`]. Useful for timestamps, PIDs, durations...
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut proj = project()?;
+    /// proj.redact_regex("[ELAPSED]", r"\d+\.\d+s")?;
+    /// let cmd = proj.command(["build"])?;
+    /// cmd.with_stdout("Finished in [ELAPSED]\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "regex")]
+    pub fn redact_regex<T: Into<String>>(&mut self, token: T, regex: &str) -> Result<&mut Self> {
+        self.redactions.push(Redaction::Regex {
+            token: token.into(),
+            regex: Regex::new(regex)?,
+        });
+        Ok(self)
+    }
+
+    /// Sets how long every command executed in this project can run before being killed. By default, there's no timeout.
+    ///
+    /// It can be overridden for a single command with [`SandboxCommand::timeout()`]. If a command runs out of time,
+    /// it returns a [`TimeoutError`] with the output captured so far.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::{error::Error, time::Duration};
+    /// # use cli_sandbox::project;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut proj = project()?;
+    /// proj.timeout(Duration::from_secs(10));
+    /// let cmd = proj.command(["wait-for-input"]); // Will fail after 10 seconds instead of hanging forever.
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub const fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Creates a new [`Project`] with the contents of a fixture directory (e.g. `tests/fixtures/my-fixture`) copied into it.
+    ///
+    /// A relative `path` is resolved against your package's root (`CARGO_MANIFEST_DIR`). See [`Project::copy_dir()`]
+    /// for how the directory is copied.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{Project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let proj = Project::from_fixture("tests/fixtures/python-project")?;
+    /// let cmd = proj.command(["build", "main.py"])?;
+    /// cmd.success();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_fixture<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let proj = Self::new()?;
+        proj.copy_dir(path, "")?;
+        Ok(proj)
+    }
+
     /// Gets the [`std::path::Path`] for the [`Project`]'s temporary directory.
     pub fn path(&self) -> &Path {
         self.tempdir.path()
     }
 
-    /// Creates a new file with a relative path to the project's directory.
+    /// Recursively copies the directory `src` into `dst`, keeping its structure, permissions and symbolic links (links
+    /// are copied as they are, not followed).
+    ///
+    /// A relative `src` is resolved against your package's root (`CARGO_MANIFEST_DIR`), and `dst` gets redirected to the
+    /// project's real path (temporary and unknown). `dst` is created if it doesn't exist, files and links already in it
+    /// are replaced.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::project;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let proj = project()?;
+    /// proj.copy_dir("tests/fixtures/config", ".config/my-cli")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn copy_dir<P: AsRef<Path>, Q: AsRef<Path>>(&self, src: P, dst: Q) -> Result<()> {
+        fixture::copy_dir(&fixture::manifest_dir().join(src), &self.path().join(dst))
+    }
+
+    /// Creates a new file with a relative path to the project's directory, along with its parent directories if they
+    /// don't exist. `contents` can be text or raw bytes (e.g. an image or an archive).
+    ///
+    /// `path` gets redirected to the project's real path (temporary and unknown).
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::project;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut proj = project()?;
+    /// proj.new_file("main.py", "print('Hello')")?;
+    /// proj.new_file("image.png", [0x89, b'P', b'N', b'G'])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_file<P: AsRef<Path>, C: AsRef<[u8]>>(&mut self, path: P, contents: C) -> Result<()> {
+        let path = self.path().join(path);
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        Ok(write(path, contents)?)
+    }
+
+    /// Creates a whole layout of files at once, from `(path, contents)` pairs. Same as calling [`Project::new_file()`]
+    /// for each of them.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::project;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut proj = project()?;
+    /// proj.new_files([
+    ///     ("Cargo.toml", "[package]\nname = \"example\""),
+    ///     ("src/main.rs", "fn main() {}"),
+    ///     ("src/nested/mod.rs", ""),
+    /// ])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_files<I, P, C>(&mut self, files: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (P, C)>,
+        P: AsRef<Path>,
+        C: AsRef<[u8]>,
+    {
+        for (path, contents) in files {
+            self.new_file(path, contents)?;
+        }
+        Ok(())
+    }
+
+    /// Creates a directory with a relative path to the project's directory, along with its parent directories if they
+    /// don't exist. It doesn't fail if the directory already exists.
     ///
     /// `path` gets redirected to the project's real path (temporary and unknown).
     #[inline]
-    pub fn new_file<P: AsRef<Path>>(&mut self, path: P, contents: &str) -> Result<()> {
-        Ok(write(self.path().join(path), contents)?)
+    pub fn mkdir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        Ok(create_dir_all(self.path().join(path))?)
     }
 
     /// Checks that the contents of a file are correct. It will panic if they aren't, and show the differences if the feature **`pretty_assertions`** is enabled
     ///
+    /// `contents` can be text or raw bytes. If either the file or `contents` isn't UTF-8, they're compared byte by byte,
+    /// and the failure shows a hex dump of both around the first differing offset.
+    ///
     /// `path` gets redirected to the project's real path (temporary and unknown)
     /// # Panics
-    /// Will panic if the contents of the file at path aren't encoded as UTF-8
-    pub fn check_file<P: AsRef<Path>>(&self, path: P, contents: &str) -> Result<()> {
+    /// Will panic if the contents of the file at path aren't the expected ones
+    pub fn check_file<P: AsRef<Path>, C: AsRef<[u8]>>(&self, path: P, contents: C) -> Result<()> {
         let mut f = File::open(self.path().join(path))?;
         let mut buf = Vec::new();
         f.read_to_end(&mut buf)?;
-        let mut buf2 = String::new();
-        buf2.push_str(match str::from_utf8(&buf) {
-            Ok(val) => val,
-            Err(_) => panic!("buf isn't UTF-8 (bug)"),
-        });
-        assert_eq!(buf2, contents);
+        let contents = contents.as_ref();
+        match (str::from_utf8(&buf), str::from_utf8(contents)) {
+            (Ok(actual), Ok(expected)) => assert_eq!(actual, expected),
+            _ => assert!(buf == contents, "{}", data::hex_diff(contents, &buf)),
+        }
+        Ok(())
+    }
+
+    /// Checks that a directory of the project has exactly the same files as `expected` (a directory in your package, e.g.
+    /// `tests/expected/my-test`), with the same contents. All the missing files, unexpected files and differences
+    /// are reported at once.
+    ///
+    /// `path` gets redirected to the project's real path (temporary and unknown), and a relative `expected` is resolved
+    /// against your package's root (`CARGO_MANIFEST_DIR`). Text files are compared like
+    /// [`WithStdout::with_stdout_data()`] (so they can contain `[..]`, `[ROOT]`...), other files byte by byte.
+    ///
+    /// If the `SANDBOX_BLESS` environment variable is set to `1`, `expected` is updated (or created) to match the
+    /// project's directory instead.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::project;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let proj = project()?;
+    /// proj.command(["generate", "--out", "generated"])?;
+    /// proj.check_dir("generated", "tests/expected/generate")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the directories don't match.
+    pub fn check_dir<P: AsRef<Path>, Q: AsRef<Path>>(&self, path: P, expected: Q) -> Result<()> {
+        self.check_dir_ignoring(path, expected, [""; 0])
+    }
+
+    /// Same as [`Project::check_dir()`], but ignoring the files that match any of the `ignore` globs, in both directories.
+    ///
+    /// Globs support `*`, `?` and `**` (any number of directories). A glob without any `/` is matched against file
+    /// names (e.g. `*.log`), and against the whole path relative to the directory otherwise (e.g. `cache/**`).
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::project;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let proj = project()?;
+    /// proj.command(["generate", "--out", "generated"])?;
+    /// proj.check_dir_ignoring("generated", "tests/expected/generate", ["*.log", "cache/**"])?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the directories don't match.
+    pub fn check_dir_ignoring<P, Q, I, S>(&self, path: P, expected: Q, ignore: I) -> Result<()>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let (path, expected) = (path.as_ref(), expected.as_ref());
+        let ignore = ignore
+            .into_iter()
+            .map(|pattern| pattern.as_ref().to_owned())
+            .collect::<Vec<_>>();
+        let substitutions = data::Substitutions {
+            root: Some(self.path()),
+            redactions: &self.redactions,
+        };
+        let actual = self.path().join(path);
+        let expected = fixture::manifest_dir().join(expected);
+        if let Some(report) = tree::compare(&actual, &expected, &ignore, substitutions)? {
+            panic!(
+                "{} doesn't match {}:\n{report}",
+                path.display(),
+                expected.display()
+            );
+        }
         Ok(())
     }
 
     /// Executes a command relative to the project's directory
-    pub fn command<I, S>(&self, args: I) -> Result<Output>
+    pub fn command<I, S>(&self, args: I) -> Result<CommandResult>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
-        #[cfg(feature = "dev")]
-        return Ok(Command::new(
-            Path::new(&std::env::var("SANDBOX_TARGET_DIR")?)
-                .join("debug")
-                .join(std::env::var("SANDBOX_PKG_NAME")?),
-        )
-        .current_dir(self.path())
-        .args(args)
-        .output()?);
-
-        #[cfg(feature = "release")]
-        return Ok(Command::new(
-            Path::new(&std::env::var("CARGO_MANIFEST_DIR")?)
-                .join("target")
-                .join("release")
-                .join(env!("CARGO_PKG_NAME")),
-        )
-        .current_dir(&self.path())
-        .args(args)
-        .output()?);
+        self.cmd().args(args).run()
+    }
+
+    /// Executes a command relative to the project's directory, like [`Project::command()`], and also returns the changes
+    /// it made to the project's files (created, deleted, modified and permission-changed files).
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use std::path::PathBuf;
+    /// # use cli_sandbox::project;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut proj = project()?;
+    /// proj.new_file("main.py", "print('Hello')")?;
+    /// let (_, changes) = proj.command_tracked(["build", "main.py"])?;
+    /// assert_eq!(changes.created, [PathBuf::from("main.rs")]);
+    /// assert!(changes.deleted.is_empty() && changes.modified.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn command_tracked<I, S>(&self, args: I) -> Result<(CommandResult, Changes)>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let before = self.snapshot()?;
+        let result = self.command(args)?;
+        let changes = before.diff(&self.snapshot()?);
+        Ok((result, changes))
+    }
+
+    /// Takes a [`Snapshot`] of the project's files (paths, sizes, hashes of their contents, permissions and modification
+    /// times), to check what changed later with [`Snapshot::diff()`].
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::project;
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let proj = project()?;
+    /// let before = proj.snapshot()?;
+    /// proj.command(["clean"])?;
+    /// let changes = before.diff(&proj.snapshot()?);
+    /// assert!(changes.is_empty(), "`clean` changed some files:\n{changes}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        Snapshot::take(self.path())
+    }
+
+    /// Creates a [`SandboxCommand`] for your binary, for when you need more control than [`Project::command()`] gives you (environment variables, stdin, working directory...)
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let proj = project()?;
+    /// let cmd = proj.cmd().arg("build").env("RUST_LOG", "debug").run()?;
+    /// cmd.with_stdout("Built!");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub const fn cmd(&self) -> SandboxCommand<'_> {
+        SandboxCommand::new(self, None)
+    }
+
+    /// Executes any program (e.g. `git` or a helper script) in the project's directory, with the same environment,
+    /// timeout and assertions as [`Project::command()`]. Useful to set up the project before running your binary, or to
+    /// check the results after it.
+    ///
+    /// `program` is searched in the `PATH` if it's just a name, and a relative path is relative to the project's
+    /// directory.
+    ///
+    /// ## Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let proj = project()?;
+    /// proj.run_program("git", ["init", "--quiet"])?.success();
+    /// proj.command(["commit-all"])?.success();
+    /// proj.run_program("git", ["log", "--format=%s"])?.with_stdout("Automatic commit\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run_program<P, I, S>(&self, program: P, args: I) -> Result<CommandResult>
+    where
+        P: AsRef<OsStr>,
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.program(program).args(args).run()
+    }
+
+    /// Creates a [`SandboxCommand`] for any program, like [`Project::run_program()`] but with all the options of
+    /// [`Project::cmd()`].
+    pub fn program<P: AsRef<OsStr>>(&self, program: P) -> SandboxCommand<'_> {
+        let program = program.as_ref();
+        // Relative paths (unlike plain names) are relative to the project, not to the tests' current directory.
+        let program = if Path::new(program).components().count() > 1 {
+            self.path().join(program).into_os_string()
+        } else {
+            program.to_owned()
+        };
+        SandboxCommand::new(self, Some(program))
     }
 
     /// Checks the [file signature](https://en.m.wikipedia.org/wiki/File_format#Magic_number) of a file and returns `true` if the file in that path is an executable.
@@ -281,6 +840,10 @@ impl Project {
     ///     - Preferred Executable Format
     /// * `00 00 03 F3`
     ///     - Amiga Hunk executable file
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the file at `path` can't be opened.
     pub fn is_bin<P: AsRef<Path>>(&self, path: P) -> bool {
         let mut buf: [u8; 8] = [0; 8];
         let mut f = File::open(self.path().join(&path)).expect("Couldn't open that path");
@@ -335,30 +898,30 @@ impl Project {
         }
     }
 
-    /// Cleans your environment used in the working directory (i.e. removing all environment variables that start with a prefix).
-    ///
-    /// ---
-    ///
-    /// (Note that "localized" environment variables are taken into account)
-    ///
-    /// # Panics
-    ///
-    /// This function will panic if it couldn't get the current working directory, or;
-    /// it can't set the current working directory to the temporary project's path, or;
-    /// it can't set the current working directory to the original working directory.
-    pub fn clean_env(&self, prefix: &str) {
-        let cwd = env::current_dir().expect("Couldn't get current working directory");
-        // Some environment variables contain some bash script for being defined in X directory (e.g. PROMPT_COMMAND='[[ $PWD == "/foo/bar/" ]] && export FOO=BAR || unset FOO').
-        // This will define FOO=BAR only if the current working directory is "/foo/bar".
-        // That's why we change the CWD
-        env::set_current_dir(self.path()).expect("Couldn't change path");
-        for (k, _) in env::vars() {
-            if k.starts_with(prefix) {
-                env::remove_var(k);
-            };
-        }
+    /// The variables of this project's environment that were set (`Some`) or removed (`None`) on top of the inherited
+    /// ones (see [`INHERITED_ENV`]).
+    pub(crate) fn env_overrides(&self) -> impl Iterator<Item = (OsString, Option<OsString>)> + '_ {
+        let removed = self
+            .inherited_envs
+            .keys()
+            .filter(|key| !self.envs.contains_key(*key))
+            .map(|key| (key.clone(), None));
+        let set = self
+            .envs
+            .iter()
+            .filter(|(key, val)| self.inherited_envs.get(*key) != Some(val))
+            .map(|(key, val)| (key.clone(), Some(val.clone())));
+        removed.chain(set)
+    }
 
-        env::set_current_dir(cwd).expect("Couldn't return to the origin directory");
+    /// Removes from the environment of this project all the variables whose name starts with `prefix`.
+    ///
+    /// Only the environment of the programs executed in this project changes, your tests' environment is left
+    /// untouched.
+    pub fn clean_env(&mut self, prefix: &str) -> &mut Self {
+        self.envs
+            .retain(|key, _| !key.to_string_lossy().starts_with(prefix));
+        self
     }
 }
 
@@ -492,6 +1055,9 @@ pub trait WithStdout {
     fn empty_stdout(&self) -> bool;
     /// Checks that the stdout is corresponding with a file (usually "<my-test>.stdout");
     ///
+    /// If the `SANDBOX_BLESS` environment variable is set to `1`, the file is updated (or created) with the actual stdout instead.
+    /// Every file that changes is reported on stderr, and on Unix, they're all listed again when the tests finish.
+    ///
     /// # Example
     ///
     /// ```no_run
@@ -507,6 +1073,9 @@ pub trait WithStdout {
     fn with_stdout_file<P: AsRef<Path>>(&self, filename: P);
     /// Checks that the stderr is corresponding with a file (usually "<my-test>.stderr");
     ///
+    /// If the `SANDBOX_BLESS` environment variable is set to `1`, the file is updated (or created) with the actual stderr instead.
+    /// Every file that changes is reported on stderr, and on Unix, they're all listed again when the tests finish.
+    ///
     /// # Example
     ///
     /// ```no_run
@@ -520,119 +1089,121 @@ pub trait WithStdout {
     /// # }
     /// ```
     fn with_stderr_file<P: AsRef<Path>>(&self, filename: P);
+    /// Checks that the standard output of a command matches the expected data, which can contain some wildcards (inspired by
+    /// `cargo-test-support`):
+    ///
+    /// * `[..]` matches any text within a line.
+    /// * A line with only `...` matches any number of lines.
+    /// * `[ROOT]` matches the project's directory (only when checking a [`CommandResult`]).
+    /// * `[EXE]` is the platform's executable suffix (`.exe` on Windows, nothing on other platforms).
+    ///
+    /// Trailing newlines are ignored. If they don't match, it will show the differences if the `pretty_asssertions` feature is enabled
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>>{
+    /// let proj = project()?;
+    /// let cmd = proj.command(["build"])?;
+    /// cmd.with_stdout_data(
+    ///     "\
+    /// Compiling [ROOT]/main.py
+    /// ...
+    /// Finished in [..]s
+    /// Output written to [ROOT]/main[EXE]
+    /// ",
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn with_stdout_data<S: AsRef<str>>(&self, expected: S);
+    /// Checks that the standard error of a command matches the expected data, using the same wildcards as [`WithStdout::with_stdout_data()`].
+    ///
+    /// ## Example
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>>{
+    /// let proj = project()?;
+    /// let cmd = proj.command(["build", "missing.py"])?;
+    /// cmd.with_stderr_data("error: couldn't read `[ROOT]/missing.py`: [..]");
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn with_stderr_data<S: AsRef<str>>(&self, expected: S);
+    /// Checks that the command exited successfully (with exit code 0), panicking with its stdout and stderr if it didn't.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let proj = project()?;
+    /// let cmd = proj.command(["my", "cool", "--args"])?;
+    /// cmd.success();
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn success(&self);
+    /// Checks that the command failed (it exited with a non-zero exit code, or was killed by a signal), panicking with its stdout and stderr if it didn't.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let proj = project()?;
+    /// let cmd = proj.command(["--invalid-flag"])?;
+    /// cmd.failure();
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn failure(&self);
+    /// Checks that the command exited with a specific exit code, panicking with its stdout and stderr if it didn't.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let proj = project()?;
+    /// let cmd = proj.command(["--invalid-flag"])?;
+    /// cmd.code(2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn code(&self, code: i32);
+    /// Checks that the command was killed by a specific signal (e.g. `SIGSEGV`, 11), panicking with its stdout and stderr if it wasn't.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::error::Error;
+    /// # use cli_sandbox::{project, WithStdout};
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let proj = project()?;
+    /// let cmd = proj.command(["segfault", "please"])?;
+    /// cmd.signal(11); // SIGSEGV
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(unix)]
+    fn signal(&self, signal: i32);
 }
 
-impl WithStdout for Output {
-    fn with_stdout<S: AsRef<str>>(&self, stdout: S) {
-        let mut buf = String::new();
-        buf.push_str(match str::from_utf8(&self.stdout) {
-            Ok(val) => val,
-            Err(_) => panic!("stdout isn't UTF-8 (bug)"),
-        });
-        assert_eq!(buf, stdout.as_ref());
-    }
-
-    fn with_stderr<S: AsRef<str>>(&self, stderr: S) {
-        let mut buf = String::new();
-        buf.push_str(match str::from_utf8(&self.stderr) {
-            Ok(val) => val,
-            Err(_) => panic!("stderr isn't UTF-8 (bug)"),
-        });
-        assert_eq!(buf, stderr.as_ref());
-    }
-
-    #[cfg(feature = "regex")]
-    fn with_stderr_regex<S: AsRef<str>>(&self, regex: S) {
-        let re = match Regex::new(regex.as_ref()) {
-            Ok(re) => re,
-            Err(e) => panic!("Regex {} isn't valid: {e}", regex.as_ref()),
-        };
-
-        let mut buf = String::new();
-        buf.push_str(match str::from_utf8(&self.stderr) {
-            Ok(val) => val,
-            Err(_) => panic!("stderr isn't UTF-8 (bug)"),
-        });
-
-        if !re.is_match(&buf) {
-            assert_eq!(buf, regex.as_ref()); // Show differences
-        };
-    }
-
-    #[cfg(feature = "regex")]
-    fn with_stdout_regex<S: AsRef<str>>(&self, regex: S) {
-        let re = match Regex::new(regex.as_ref()) {
-            Ok(re) => re,
-            Err(e) => panic!("Regex {} isn't valid: {e}", regex.as_ref()),
-        };
-
-        let mut buf = String::new();
-        buf.push_str(match str::from_utf8(&self.stdout) {
-            Ok(val) => val,
-            Err(_) => panic!("stdout isn't UTF-8 (bug)"),
-        });
-
-        if !re.is_match(&buf) {
-            assert_eq!(buf, regex.as_ref()); // Show differences
-        };
+/// Describes how a command exited, for failure messages.
+pub(crate) fn describe_status(status: ExitStatus) -> String {
+    #[cfg(unix)]
+    if let Some(signal) = os::unix::process::ExitStatusExt::signal(&status) {
+        return format!("was killed by signal {signal}");
     }
-
-    fn stdout_warns(&self) -> bool {
-        let mut buf = String::new();
-        buf.push_str(match str::from_utf8(&self.stdout) {
-            Ok(val) => val,
-            Err(_) => panic!("stdout isn't UTF-8 (bug)"),
-        });
-        buf.contains("warnings:")
-    }
-
-    fn stderr_warns(&self) -> bool {
-        let mut buf = String::new();
-        buf.push_str(match str::from_utf8(&self.stderr) {
-            Ok(val) => val,
-            Err(_) => panic!("stderr isn't UTF-8 (bug)"),
-        });
-        buf.contains("warnings:")
-    }
-
-    #[inline]
-    fn empty_stderr(&self) -> bool {
-        self.stdout.is_empty()
-    }
-
-    #[inline]
-    fn empty_stdout(&self) -> bool {
-        self.stdout.is_empty()
-    }
-
-    fn with_stdout_file<P: AsRef<Path>>(&self, filename: P) {
-        let expected = match std::fs::read_to_string(&filename) {
-            Ok(s) => s,
-            Err(e) => panic!("Couldn't read file {}: {e}", filename.as_ref().display()),
-        };
-
-        let mut buf = String::new();
-        buf.push_str(match str::from_utf8(&self.stdout) {
-            Ok(val) => val,
-            Err(_) => panic!("stdout isn't UTF-8 (bug)"),
-        });
-
-        assert_eq!(expected, buf);
-    }
-
-    fn with_stderr_file<P: AsRef<Path>>(&self, filename: P) {
-        let expected = match std::fs::read_to_string(&filename) {
-            Ok(s) => s,
-            Err(e) => panic!("Couldn't read file {}: {e}", filename.as_ref().display()),
-        };
-
-        let mut buf = String::new();
-        buf.push_str(match str::from_utf8(&self.stderr) {
-            Ok(val) => val,
-            Err(_) => panic!("stderr isn't UTF-8 (bug)"),
-        });
-
-        assert_eq!(expected, buf);
+    match status.code() {
+        Some(code) => format!("exited with code {code}"),
+        None => "exited without an exit code".into(),
     }
 }
 
@@ -0,0 +1,227 @@
+//! Checking the command transcripts in Markdown files, see [`check_markdown()`].
+
+use std::{fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    data::{self, Substitutions},
+    fixture, Project,
+};
+
+/// A command found in a `console` code block, along with the output that is expected from it.
+#[derive(Debug)]
+struct Transcript {
+    /// The line of the Markdown file where the command is.
+    line: usize,
+    command: String,
+    expected: String,
+}
+
+/// Checks that the commands in the `console` code blocks of a Markdown file (e.g. your `README.md`) produce the output
+/// written below them, so your documentation can't drift from how your binary really behaves. A relative `path` is
+/// resolved against the root of your package (`CARGO_MANIFEST_DIR`), not the current directory.
+///
+/// Lines starting with `$ ` are commands: the first word is your binary (whatever it's called in the file), and the rest
+/// are its arguments (quotes and `\` escapes are supported). The lines below a command, up to the next one, are its
+/// expected output: its stdout followed by its stderr, matched with the same wildcards and normalization as
+/// [`WithStdout::with_stdout_data()`](crate::WithStdout::with_stdout_data) (`[..]`, `...`, `[ROOT]`, `[EXE]`).
+///
+/// Each command is executed in its own [`Project`], the same way [`Project::command()`] does (remember to call
+/// [`init()`](crate::init) before). All the commands are executed, and then every failure is reported at once.
+///
+/// ````markdown
+/// ```console
+/// $ my-cli greet "Ferris the crab"
+/// Hello, Ferris the crab!
+/// $ my-cli --version
+/// my-cli [..]
+/// ```
+/// ````
+///
+/// ## Example
+///
+/// ```no_run
+/// #[test]
+/// fn readme() {
+///     cli_sandbox::init();
+///     cli_sandbox::check_markdown("README.md");
+/// }
+/// ```
+///
+/// # Panics
+///
+/// This function will panic if the file can't be read, if it doesn't contain any command, or if any command fails to
+/// execute or doesn't produce the expected output.
+pub fn check_markdown<P: AsRef<Path>>(path: P) {
+    let path = path.as_ref();
+    let markdown = match fs::read_to_string(fixture::manifest_dir().join(path)) {
+        Ok(markdown) => markdown,
+        Err(e) => panic!("Couldn't read file {}: {e}", path.display()),
+    };
+    let transcripts = transcripts(&markdown);
+    if transcripts.is_empty() {
+        panic!("{} doesn't have any command to check", path.display());
+    }
+
+    let failures = transcripts
+        .iter()
+        .filter_map(|transcript| {
+            run(transcript)
+                .unwrap_or_else(|e| Some(format!("{e:#}")))
+                .map(|failure| {
+                    format!(
+                        "--- {}:{}: $ {}\n{failure}",
+                        path.display(),
+                        transcript.line,
+                        transcript.command
+                    )
+                })
+        })
+        .collect::<Vec<_>>();
+
+    if !failures.is_empty() {
+        panic!(
+            "{} of {} commands failed:\n\n{}",
+            failures.len(),
+            transcripts.len(),
+            failures.join("\n\n")
+        );
+    }
+}
+
+/// Runs the command of a transcript, returning why it failed (if it did).
+fn run(transcript: &Transcript) -> Result<Option<String>> {
+    let args = split(&transcript.command)?;
+    let proj = Project::new()?;
+    // The first word is the binary, which is always the one being tested.
+    let result = proj.command(args.iter().skip(1))?;
+
+    let mut actual = String::from_utf8_lossy(&result.stdout).into_owned();
+    actual.push_str(&String::from_utf8_lossy(&result.stderr));
+    let substitutions = Substitutions {
+        root: Some(proj.path()),
+        redactions: &[],
+    };
+    Ok(substitutions
+        .compare(&transcript.expected, &actual)
+        .err()
+        .map(|(expected, actual)| {
+            format!(
+                "Running `{}`\nUnexpected output:\n{}",
+                result.invocation(),
+                data::diff(&expected, &actual)
+            )
+        }))
+}
+
+/// Extracts the commands, and their expected output, from the `console` code blocks of a Markdown file.
+fn transcripts(markdown: &str) -> Vec<Transcript> {
+    let mut transcripts = Vec::new();
+    // The fence that opened the current code block (if we're in one), and whether it's a `console` block.
+    let mut fence: Option<(Fence, bool)> = None;
+    // How many commands were found before the current block, so output can't continue from a previous block.
+    let mut block_start = 0;
+
+    for (i, line) in markdown.lines().enumerate() {
+        let trimmed = line.trim_start();
+        match fence {
+            Some((open, _))
+                if Fence::parse(trimmed).is_some_and(|(close, info)| {
+                    close.marker == open.marker && close.len >= open.len && info.is_empty()
+                }) =>
+            {
+                fence = None;
+            }
+            Some((_, true)) => {
+                if let Some(command) = trimmed.strip_prefix("$ ") {
+                    transcripts.push(Transcript {
+                        line: i + 1,
+                        command: command.trim().to_owned(),
+                        expected: String::new(),
+                    });
+                } else if transcripts.len() > block_start {
+                    if let Some(transcript) = transcripts.last_mut() {
+                        transcript.expected.push_str(line);
+                        transcript.expected.push('\n');
+                    }
+                }
+            }
+            Some((_, false)) => {}
+            None => {
+                if let Some((open, info)) = Fence::parse(trimmed) {
+                    let console = info.split([' ', ',']).next() == Some("console");
+                    fence = Some((open, console));
+                    block_start = transcripts.len();
+                }
+            }
+        }
+    }
+
+    transcripts
+}
+
+/// A code fence: a run of at least three backticks or tildes.
+#[derive(Debug, Clone, Copy)]
+struct Fence {
+    marker: char,
+    len: usize,
+}
+
+impl Fence {
+    /// Parses the fence a (left-trimmed) line starts with, along with the info string after it.
+    fn parse(line: &str) -> Option<(Self, &str)> {
+        let marker = line.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+        let len = line.len() - line.trim_start_matches(marker).len();
+        (len >= 3).then(|| (Self { marker, len }, line[len..].trim()))
+    }
+}
+
+/// Splits a command line into words, like a (very) simple shell would.
+fn split(command: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => bail!("Unterminated single quote in `{command}`"),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => bail!("Unterminated double quote in `{command}`"),
+                        },
+                        Some(c) => word.push(c),
+                        None => bail!("Unterminated double quote in `{command}`"),
+                    }
+                }
+            }
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .with_context(|| format!("Trailing `\\` in `{command}`"))?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
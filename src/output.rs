@@ -0,0 +1,356 @@
+//! Implementation of the [`WithStdout`] assertions, shared by [`Output`] and [`CommandResult`].
+
+use std::{borrow::Cow, path::Path, process::Output, str};
+
+#[cfg(feature = "pretty_assertions")]
+use pretty_assertions::assert_eq;
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+use crate::{bless, data::Substitutions, describe_status, CommandResult, WithStdout};
+
+/// The output of a command, along with the context needed to give useful failure messages about it.
+struct Checker<'a> {
+    output: &'a Output,
+    /// The command line that produced the output, when it's known.
+    invocation: Option<String>,
+    /// How to normalize the output before comparing it, when the project it was executed in is known.
+    substitutions: Option<Substitutions<'a>>,
+}
+
+impl<'a> Checker<'a> {
+    const fn new(output: &'a Output) -> Self {
+        Self {
+            output,
+            invocation: None,
+            substitutions: None,
+        }
+    }
+
+    /// Normalizes `text` (see [`Substitutions::normalize()`]) if the command was executed in a known project, so that
+    /// both the expected and actual output can contain tokens like `[ROOT]`.
+    fn normalize<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        match &self.substitutions {
+            Some(substitutions) => Cow::Owned(substitutions.normalize(text)),
+            None => Cow::Borrowed(text),
+        }
+    }
+
+    fn stdout(&self) -> &'a str {
+        match str::from_utf8(&self.output.stdout) {
+            Ok(val) => val,
+            Err(_) => panic!("{} isn't UTF-8 (bug)", self.context("stdout")),
+        }
+    }
+
+    fn stderr(&self) -> &'a str {
+        match str::from_utf8(&self.output.stderr) {
+            Ok(val) => val,
+            Err(_) => panic!("{} isn't UTF-8 (bug)", self.context("stderr")),
+        }
+    }
+
+    /// Describes `what` (e.g. "stdout") along with the command that produced it.
+    fn context(&self, what: &str) -> String {
+        match &self.invocation {
+            Some(invocation) => format!("{what} of `{invocation}`"),
+            None => what.into(),
+        }
+    }
+
+    /// Panics with a report of the command's exit status and output.
+    fn status_mismatch(&self, expected: &str) -> ! {
+        let command = match &self.invocation {
+            Some(invocation) => format!("`{invocation}`"),
+            None => "the command".into(),
+        };
+        panic!(
+            "Expected {command} to {expected}, but it {}\n--- stdout\n{}\n--- stderr\n{}",
+            describe_status(self.output.status),
+            String::from_utf8_lossy(&self.output.stdout),
+            String::from_utf8_lossy(&self.output.stderr),
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    fn with_regex(&self, what: &str, buf: &str, regex: &str) {
+        let re = match Regex::new(regex) {
+            Ok(re) => re,
+            Err(e) => panic!("Regex {regex} isn't valid: {e}"),
+        };
+
+        if !re.is_match(buf) {
+            assert_eq!(buf, regex, "{}", self.context(what)); // Show differences
+        };
+    }
+
+    fn with_data(&self, what: &str, buf: &str, expected: &str) {
+        let substitutions = self.substitutions.unwrap_or_default();
+        if let Err((expected, actual)) = substitutions.compare(expected, buf) {
+            assert_eq!(actual, expected, "{}", self.context(what)); // Show differences
+        }
+    }
+
+    fn with_file(&self, what: &str, buf: &str, filename: &Path) {
+        if bless::enabled() {
+            let actual = self.normalize(buf);
+            let outdated =
+                std::fs::read_to_string(filename).map_or(true, |expected| expected != actual);
+            if outdated {
+                if let Err(e) = bless::write(filename, actual.as_bytes()) {
+                    panic!("{e:#}");
+                }
+            }
+            return;
+        }
+
+        let expected = match std::fs::read_to_string(filename) {
+            Ok(s) => s,
+            Err(e) => panic!("Couldn't read file {}: {e}", filename.display()),
+        };
+
+        assert_eq!(expected, self.normalize(buf), "{}", self.context(what));
+    }
+}
+
+impl WithStdout for Checker<'_> {
+    fn with_stdout<S: AsRef<str>>(&self, stdout: S) {
+        assert_eq!(
+            self.normalize(self.stdout()),
+            stdout.as_ref(),
+            "{}",
+            self.context("stdout")
+        );
+    }
+
+    fn with_stderr<S: AsRef<str>>(&self, stderr: S) {
+        assert_eq!(
+            self.normalize(self.stderr()),
+            stderr.as_ref(),
+            "{}",
+            self.context("stderr")
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    fn with_stdout_regex<S: AsRef<str>>(&self, regex: S) {
+        self.with_regex("stdout", self.stdout(), regex.as_ref());
+    }
+
+    #[cfg(feature = "regex")]
+    fn with_stderr_regex<S: AsRef<str>>(&self, regex: S) {
+        self.with_regex("stderr", self.stderr(), regex.as_ref());
+    }
+
+    fn stdout_warns(&self) -> bool {
+        self.stdout().contains("warnings:")
+    }
+
+    fn stderr_warns(&self) -> bool {
+        self.stderr().contains("warnings:")
+    }
+
+    fn empty_stderr(&self) -> bool {
+        self.output.stderr.is_empty()
+    }
+
+    fn empty_stdout(&self) -> bool {
+        self.output.stdout.is_empty()
+    }
+
+    fn with_stdout_file<P: AsRef<Path>>(&self, filename: P) {
+        self.with_file("stdout", self.stdout(), filename.as_ref());
+    }
+
+    fn with_stderr_file<P: AsRef<Path>>(&self, filename: P) {
+        self.with_file("stderr", self.stderr(), filename.as_ref());
+    }
+
+    fn with_stdout_data<S: AsRef<str>>(&self, expected: S) {
+        self.with_data("stdout", self.stdout(), expected.as_ref());
+    }
+
+    fn with_stderr_data<S: AsRef<str>>(&self, expected: S) {
+        self.with_data("stderr", self.stderr(), expected.as_ref());
+    }
+
+    fn success(&self) {
+        if !self.output.status.success() {
+            self.status_mismatch("succeed");
+        }
+    }
+
+    fn failure(&self) {
+        if self.output.status.success() {
+            self.status_mismatch("fail");
+        }
+    }
+
+    fn code(&self, code: i32) {
+        if self.output.status.code() != Some(code) {
+            self.status_mismatch(&format!("exit with code {code}"));
+        }
+    }
+
+    #[cfg(unix)]
+    fn signal(&self, signal: i32) {
+        if std::os::unix::process::ExitStatusExt::signal(&self.output.status) != Some(signal) {
+            self.status_mismatch(&format!("be killed by signal {signal}"));
+        }
+    }
+}
+
+impl WithStdout for Output {
+    fn with_stdout<S: AsRef<str>>(&self, stdout: S) {
+        Checker::new(self).with_stdout(stdout);
+    }
+
+    fn with_stderr<S: AsRef<str>>(&self, stderr: S) {
+        Checker::new(self).with_stderr(stderr);
+    }
+
+    #[cfg(feature = "regex")]
+    fn with_stdout_regex<S: AsRef<str>>(&self, regex: S) {
+        Checker::new(self).with_stdout_regex(regex);
+    }
+
+    #[cfg(feature = "regex")]
+    fn with_stderr_regex<S: AsRef<str>>(&self, regex: S) {
+        Checker::new(self).with_stderr_regex(regex);
+    }
+
+    fn stdout_warns(&self) -> bool {
+        Checker::new(self).stdout_warns()
+    }
+
+    fn stderr_warns(&self) -> bool {
+        Checker::new(self).stderr_warns()
+    }
+
+    #[inline]
+    fn empty_stderr(&self) -> bool {
+        Checker::new(self).empty_stderr()
+    }
+
+    #[inline]
+    fn empty_stdout(&self) -> bool {
+        Checker::new(self).empty_stdout()
+    }
+
+    fn with_stdout_file<P: AsRef<Path>>(&self, filename: P) {
+        Checker::new(self).with_stdout_file(filename);
+    }
+
+    fn with_stderr_file<P: AsRef<Path>>(&self, filename: P) {
+        Checker::new(self).with_stderr_file(filename);
+    }
+
+    fn with_stdout_data<S: AsRef<str>>(&self, expected: S) {
+        Checker::new(self).with_stdout_data(expected);
+    }
+
+    fn with_stderr_data<S: AsRef<str>>(&self, expected: S) {
+        Checker::new(self).with_stderr_data(expected);
+    }
+
+    fn success(&self) {
+        Checker::new(self).success();
+    }
+
+    fn failure(&self) {
+        Checker::new(self).failure();
+    }
+
+    fn code(&self, code: i32) {
+        Checker::new(self).code(code);
+    }
+
+    #[cfg(unix)]
+    fn signal(&self, signal: i32) {
+        Checker::new(self).signal(signal);
+    }
+}
+
+impl CommandResult {
+    fn checker(&self) -> Checker<'_> {
+        Checker {
+            output: self.output(),
+            invocation: Some(self.invocation()),
+            substitutions: Some(Substitutions {
+                root: Some(self.root()),
+                redactions: &self.redactions,
+            }),
+        }
+    }
+}
+
+impl WithStdout for CommandResult {
+    fn with_stdout<S: AsRef<str>>(&self, stdout: S) {
+        self.checker().with_stdout(stdout);
+    }
+
+    fn with_stderr<S: AsRef<str>>(&self, stderr: S) {
+        self.checker().with_stderr(stderr);
+    }
+
+    #[cfg(feature = "regex")]
+    fn with_stdout_regex<S: AsRef<str>>(&self, regex: S) {
+        self.checker().with_stdout_regex(regex);
+    }
+
+    #[cfg(feature = "regex")]
+    fn with_stderr_regex<S: AsRef<str>>(&self, regex: S) {
+        self.checker().with_stderr_regex(regex);
+    }
+
+    fn stdout_warns(&self) -> bool {
+        self.checker().stdout_warns()
+    }
+
+    fn stderr_warns(&self) -> bool {
+        self.checker().stderr_warns()
+    }
+
+    #[inline]
+    fn empty_stderr(&self) -> bool {
+        self.checker().empty_stderr()
+    }
+
+    #[inline]
+    fn empty_stdout(&self) -> bool {
+        self.checker().empty_stdout()
+    }
+
+    fn with_stdout_file<P: AsRef<Path>>(&self, filename: P) {
+        self.checker().with_stdout_file(filename);
+    }
+
+    fn with_stderr_file<P: AsRef<Path>>(&self, filename: P) {
+        self.checker().with_stderr_file(filename);
+    }
+
+    fn with_stdout_data<S: AsRef<str>>(&self, expected: S) {
+        self.checker().with_stdout_data(expected);
+    }
+
+    fn with_stderr_data<S: AsRef<str>>(&self, expected: S) {
+        self.checker().with_stderr_data(expected);
+    }
+
+    fn success(&self) {
+        self.checker().success();
+    }
+
+    fn failure(&self) {
+        self.checker().failure();
+    }
+
+    fn code(&self, code: i32) {
+        self.checker().code(code);
+    }
+
+    #[cfg(unix)]
+    fn signal(&self, signal: i32) {
+        self.checker().signal(signal);
+    }
+}
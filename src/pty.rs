@@ -0,0 +1,309 @@
+//! Running programs attached to a pseudo-terminal, see [`SandboxCommand::spawn_pty()`].
+
+use std::{
+    ffi::{CStr, OsStr},
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::{ffi::OsStrExt, fs::OpenOptionsExt, process::CommandExt},
+    },
+    process::{Child, ExitStatus, Stdio},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+use crate::{command::kill, expect::Exchange, SandboxCommand};
+
+/// The size (rows and columns) of a new pseudo-terminal.
+const DEFAULT_SIZE: (u16, u16) = (24, 80);
+
+/// A program running attached to a pseudo-terminal (PTY), created with [`SandboxCommand::spawn_pty()`].
+///
+/// The program sees a real terminal on its stdin, stdout and stderr, so it behaves as it does when a user runs it:
+/// prompts, password inputs, colors, TUIs... Its output (stdout and stderr together, as a terminal shows them, including
+/// the echo of the input) is read with [`PtySession::expect()`] and friends, and input is sent as if typed on a keyboard.
+///
+/// Each expectation waits up to the command's timeout (see [`SandboxCommand::timeout()`]), or 10 seconds if it doesn't
+/// have one. It can be changed for all the following ones with [`PtySession::timeout()`], or for a single one with
+/// [`PtySession::expect_timeout()`] (and the other `_timeout` variants). If the session is dropped while the program is
+/// still running, it gets killed.
+///
+/// ## Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # use cli_sandbox::{project, Key};
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let proj = project()?;
+/// let mut session = proj.cmd().arg("init").spawn_pty()?;
+/// session.expect("Project name: ")?;
+/// session.send_line("my-project")?;
+/// session.expect("Overwrite existing files? [y/N]")?;
+/// session.send_key(Key::Enter)?;
+/// assert!(session.wait()?.success());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PtySession {
+    child: Child,
+    master: File,
+    exchange: Exchange,
+}
+
+/// A key (or key combination) to send to a program running in a terminal, see [`PtySession::send_key()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    Up,
+    Down,
+    Right,
+    Left,
+    Home,
+    End,
+    Delete,
+    /// Usually interrupts the program.
+    CtrlC,
+    /// Usually means the end of the input.
+    CtrlD,
+}
+
+impl Key {
+    /// The bytes a terminal sends for this key.
+    const fn bytes(self) -> &'static [u8] {
+        match self {
+            Self::Enter => b"\r",
+            Self::Tab => b"\t",
+            Self::Backspace => b"\x7f",
+            Self::Escape => b"\x1b",
+            Self::Up => b"\x1b[A",
+            Self::Down => b"\x1b[B",
+            Self::Right => b"\x1b[C",
+            Self::Left => b"\x1b[D",
+            Self::Home => b"\x1b[H",
+            Self::End => b"\x1b[F",
+            Self::Delete => b"\x1b[3~",
+            Self::CtrlC => b"\x03",
+            Self::CtrlD => b"\x04",
+        }
+    }
+}
+
+impl PtySession {
+    pub(crate) fn spawn(cmd: &SandboxCommand<'_>) -> Result<Self> {
+        let (master, slave) = open(DEFAULT_SIZE)?;
+        let (program, _, mut command) = cmd.command()?;
+        if !cmd.sets_env("TERM") {
+            command.env("TERM", "xterm-256color");
+        }
+        command
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave));
+        // SAFETY: the closure only calls `setsid` and `ioctl`, which are async-signal-safe, so they can be called between
+        // `fork` and `exec`.
+        unsafe {
+            command.pre_exec(|| {
+                // Start a new session (which also puts the program in its own process group, so it can be killed along
+                // with everything it spawns), and make the PTY (already its stdin) its controlling terminal.
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            })
+        };
+        let child = command.spawn().with_context(|| cmd.spawn_error(&program))?;
+        // The command keeps the program's end of the PTY open, which would prevent us from seeing the end of the output.
+        drop(command);
+
+        let output = master.try_clone()?;
+        Ok(Self {
+            child,
+            master,
+            exchange: Exchange::new(output, cmd.expect_timeout()),
+        })
+    }
+
+    /// Sets how long all the following expectations (and [`PtySession::wait()`]) wait before failing, see
+    /// [`PtySession::expect_timeout()`] to change it for a single one.
+    pub const fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.exchange.timeout = timeout;
+        self
+    }
+
+    /// Waits until `text` appears in the output, returning all the output until it (included). The output that is
+    /// returned is consumed, so following expectations only look at what comes after it.
+    ///
+    /// ## Errors
+    ///
+    /// Apart from I/O errors, this returns an [`ExpectError`](crate::ExpectError) (with a transcript of the session) if
+    /// `text` doesn't appear before the timeout, or before the output ends.
+    pub fn expect(&mut self, text: &str) -> Result<String> {
+        self.expect_timeout(text, self.exchange.timeout)
+    }
+
+    /// Same as [`PtySession::expect()`], but waiting up to `timeout` (instead of the session's timeout) for this
+    /// expectation only.
+    ///
+    /// ## Errors
+    ///
+    /// Same as [`PtySession::expect()`].
+    pub fn expect_timeout(&mut self, text: &str, timeout: Duration) -> Result<String> {
+        Ok(self
+            .exchange
+            .expect(&format!("`{text}`"), timeout, |output, _| {
+                output.find(text).map(|i| i + text.len())
+            })?)
+    }
+
+    /// Same as [`PtySession::expect()`], but waiting until the output matches `regex`.
+    ///
+    /// ## Errors
+    ///
+    /// Same as [`PtySession::expect()`], or if `regex` isn't a valid regular expression.
+    #[cfg(feature = "regex")]
+    pub fn expect_regex(&mut self, regex: &str) -> Result<String> {
+        self.expect_regex_timeout(regex, self.exchange.timeout)
+    }
+
+    /// Same as [`PtySession::expect_regex()`], but waiting up to `timeout` (instead of the session's timeout) for this
+    /// expectation only.
+    ///
+    /// ## Errors
+    ///
+    /// Same as [`PtySession::expect_regex()`].
+    #[cfg(feature = "regex")]
+    pub fn expect_regex_timeout(&mut self, regex: &str, timeout: Duration) -> Result<String> {
+        let regex = Regex::new(regex)?;
+        Ok(self
+            .exchange
+            .expect(&format!("a match of `{regex}`"), timeout, |output, _| {
+                regex.find(output).map(|m| m.end())
+            })?)
+    }
+
+    /// Waits until the output ends (usually because the program exited), returning the rest of the output.
+    ///
+    /// ## Errors
+    ///
+    /// Apart from I/O errors, this returns an [`ExpectError`](crate::ExpectError) (with a transcript of the session) if
+    /// the output doesn't end before the timeout.
+    pub fn expect_eof(&mut self) -> Result<String> {
+        self.expect_eof_timeout(self.exchange.timeout)
+    }
+
+    /// Same as [`PtySession::expect_eof()`], but waiting up to `timeout` (instead of the session's timeout) for this
+    /// expectation only.
+    ///
+    /// ## Errors
+    ///
+    /// Same as [`PtySession::expect_eof()`].
+    pub fn expect_eof_timeout(&mut self, timeout: Duration) -> Result<String> {
+        Ok(self
+            .exchange
+            .expect("the end of the output", timeout, |output, eof| {
+                eof.then_some(output.len())
+            })?)
+    }
+
+    /// Sends `input` to the program, as if it was typed.
+    pub fn send<B: AsRef<[u8]>>(&mut self, input: B) -> Result<()> {
+        let input = input.as_ref();
+        self.master.write_all(input)?;
+        self.master.flush()?;
+        self.exchange.record_input(input);
+        Ok(())
+    }
+
+    /// Sends a line to the program, as if it was typed and followed by <kbd>Enter</kbd>.
+    pub fn send_line(&mut self, line: &str) -> Result<()> {
+        self.send(format!("{line}\r"))
+    }
+
+    /// Sends a key (or key combination) to the program, e.g. [`Key::Up`] or [`Key::CtrlC`].
+    pub fn send_key(&mut self, key: Key) -> Result<()> {
+        self.send(key.bytes())
+    }
+
+    /// Resizes the terminal, the program gets notified with a `SIGWINCH` signal.
+    pub fn resize(&mut self, rows: u16, cols: u16) -> Result<()> {
+        set_size(&self.master, rows, cols)
+    }
+
+    /// Waits for the program to exit, returning its exit status. If it's still running after the timeout, it gets
+    /// killed and an [`ExpectError`](crate::ExpectError) is returned.
+    pub fn wait(&mut self) -> Result<ExitStatus> {
+        self.exchange.wait(&mut self.child)
+    }
+
+    /// Everything that was exchanged with the program until now: its output and the input sent to it, in order.
+    pub fn transcript(&mut self) -> String {
+        self.exchange.transcript()
+    }
+}
+
+impl Drop for PtySession {
+    fn drop(&mut self) {
+        if matches!(self.child.try_wait(), Ok(None)) {
+            kill(&mut self.child);
+        }
+    }
+}
+
+/// Opens a new pseudo-terminal, returning both of its ends (the one we use and the one for the program).
+///
+/// `openpty` can't open them with `FD_CLOEXEC`, and a program spawned (e.g. by another test) before setting it would
+/// inherit them and keep the PTY open, so we'd never see the end of the output. `File` always opens with `O_CLOEXEC`,
+/// which sets it atomically.
+fn open((rows, cols): (u16, u16)) -> Result<(File, OwnedFd)> {
+    let master = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open("/dev/ptmx")?;
+    // SAFETY: the file descriptor is open (we own it).
+    if unsafe { libc::unlockpt(master.as_raw_fd()) } == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let mut name = [0; 64];
+    // SAFETY: the file descriptor is open, and `name` is valid for writes of its length for the duration of the call.
+    let errno = unsafe { libc::ptsname_r(master.as_raw_fd(), name.as_mut_ptr(), name.len()) };
+    if errno != 0 {
+        return Err(io::Error::from_raw_os_error(errno).into());
+    }
+    // SAFETY: `ptsname_r` succeeded, so `name` holds a nul-terminated string.
+    let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+    let slave = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(OsStr::from_bytes(name.to_bytes()))?;
+    set_size(&master, rows, cols)?;
+    Ok((master, slave.into()))
+}
+
+/// Sets the size of the terminal `master` is the controlling end of.
+fn set_size(master: &File, rows: u16, cols: u16) -> Result<()> {
+    let size = winsize(rows, cols);
+    // SAFETY: the file descriptor is open and `size` is a valid `winsize` for the duration of the call.
+    if unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size) } == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+const fn winsize(rows: u16, cols: u16) -> libc::winsize {
+    libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
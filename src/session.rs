@@ -0,0 +1,235 @@
+//! Scripted interaction with a running program over pipes, see [`SandboxCommand::spawn()`].
+
+use std::{
+    io::Write,
+    process::{Child, ChildStdin, ExitStatus, Stdio},
+    time::Duration,
+};
+
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+
+use anyhow::{anyhow, Context, Result};
+#[cfg(feature = "regex")]
+use regex::Regex;
+
+use crate::{
+    command::{capture, kill, Capture},
+    expect::{Exchange, ExpectError},
+    SandboxCommand,
+};
+
+/// A running program whose stdin and stdout are connected to pipes, created with [`SandboxCommand::spawn()`]. Useful for
+/// REPL-style programs that don't need a terminal (see [`PtySession`](crate::PtySession) for the ones that do).
+///
+/// Its stdout is read with [`Session::expect()`] and friends, and input is sent to its stdin with [`Session::send()`] and
+/// [`Session::send_line()`]. Its stderr is collected apart, see [`Session::stderr()`].
+///
+/// Each expectation waits up to the command's timeout (see [`SandboxCommand::timeout()`]), or 10 seconds if it doesn't
+/// have one. It can be changed for all the following ones with [`Session::timeout()`], or for a single one with
+/// [`Session::expect_timeout()`] (and the other `_timeout` variants). If the session is dropped while the program is
+/// still running, it gets killed.
+///
+/// ## Example
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # use std::time::Duration;
+/// # use cli_sandbox::project;
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let proj = project()?;
+/// let mut session = proj.cmd().arg("repl").spawn()?;
+/// session.expect("prompt> ")?;
+/// session.send_line("help")?;
+/// session.expect("Available commands:")?;
+/// session.expect("prompt> ")?;
+/// session.send_line("build --all")?;
+/// session.expect_timeout("Build finished", Duration::from_secs(60))?;
+/// session.send_line("exit")?;
+/// session.expect_eof()?;
+/// assert!(session.wait()?.success());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Session {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    exchange: Exchange,
+    stderr: Capture,
+}
+
+impl Session {
+    pub(crate) fn spawn(cmd: &SandboxCommand<'_>) -> Result<Self> {
+        let (program, _, mut command) = cmd.command()?;
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // Put the program in its own process group, so we can kill everything it spawned if it's left running.
+        #[cfg(unix)]
+        command.process_group(0);
+
+        let mut child = command.spawn().with_context(|| cmd.spawn_error(&program))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("The program's stdout isn't piped"))?;
+        let stderr = capture(child.stderr.take());
+        Ok(Self {
+            stdin: child.stdin.take(),
+            child,
+            exchange: Exchange::new(stdout, cmd.expect_timeout()),
+            stderr,
+        })
+    }
+
+    /// Sets how long all the following expectations (and [`Session::wait()`]) wait before failing, see
+    /// [`Session::expect_timeout()`] to change it for a single one.
+    pub const fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.exchange.timeout = timeout;
+        self
+    }
+
+    /// Waits until `text` appears in stdout, returning all the output until it (included). The output that is returned
+    /// is consumed, so following expectations only look at what comes after it.
+    ///
+    /// ## Errors
+    ///
+    /// Apart from I/O errors, this returns an [`ExpectError`] (with a transcript of the session) if `text` doesn't
+    /// appear before the timeout, or before stdout is closed.
+    pub fn expect(&mut self, text: &str) -> Result<String> {
+        self.expect_timeout(text, self.exchange.timeout)
+    }
+
+    /// Same as [`Session::expect()`], but waiting up to `timeout` (instead of the session's timeout) for this
+    /// expectation only.
+    ///
+    /// ## Errors
+    ///
+    /// Same as [`Session::expect()`].
+    pub fn expect_timeout(&mut self, text: &str, timeout: Duration) -> Result<String> {
+        let found = self
+            .exchange
+            .expect(&format!("`{text}`"), timeout, |output, _| {
+                output.find(text).map(|i| i + text.len())
+            });
+        self.with_stderr(found)
+    }
+
+    /// Same as [`Session::expect()`], but waiting until the output matches `regex`.
+    ///
+    /// ## Errors
+    ///
+    /// Same as [`Session::expect()`], or if `regex` isn't a valid regular expression.
+    #[cfg(feature = "regex")]
+    pub fn expect_regex(&mut self, regex: &str) -> Result<String> {
+        self.expect_regex_timeout(regex, self.exchange.timeout)
+    }
+
+    /// Same as [`Session::expect_regex()`], but waiting up to `timeout` (instead of the session's timeout) for this
+    /// expectation only.
+    ///
+    /// ## Errors
+    ///
+    /// Same as [`Session::expect_regex()`].
+    #[cfg(feature = "regex")]
+    pub fn expect_regex_timeout(&mut self, regex: &str, timeout: Duration) -> Result<String> {
+        let regex = Regex::new(regex)?;
+        let found = self
+            .exchange
+            .expect(&format!("a match of `{regex}`"), timeout, |output, _| {
+                regex.find(output).map(|m| m.end())
+            });
+        self.with_stderr(found)
+    }
+
+    /// Waits until stdout is closed (usually because the program exited), returning the rest of the output.
+    ///
+    /// ## Errors
+    ///
+    /// Apart from I/O errors, this returns an [`ExpectError`] (with a transcript of the session) if stdout isn't
+    /// closed before the timeout.
+    pub fn expect_eof(&mut self) -> Result<String> {
+        self.expect_eof_timeout(self.exchange.timeout)
+    }
+
+    /// Same as [`Session::expect_eof()`], but waiting up to `timeout` (instead of the session's timeout) for this
+    /// expectation only.
+    ///
+    /// ## Errors
+    ///
+    /// Same as [`Session::expect_eof()`].
+    pub fn expect_eof_timeout(&mut self, timeout: Duration) -> Result<String> {
+        let found = self
+            .exchange
+            .expect("the end of the output", timeout, |output, eof| {
+                eof.then_some(output.len())
+            });
+        self.with_stderr(found)
+    }
+
+    /// Writes `input` to the program's stdin.
+    ///
+    /// ## Errors
+    ///
+    /// Apart from I/O errors (e.g. the program exited), this fails if stdin was closed with [`Session::close_stdin()`].
+    pub fn send<B: AsRef<[u8]>>(&mut self, input: B) -> Result<()> {
+        let input = input.as_ref();
+        let stdin = self
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow!("The program's stdin was already closed"))?;
+        stdin.write_all(input)?;
+        stdin.flush()?;
+        self.exchange.record_input(input);
+        Ok(())
+    }
+
+    /// Writes a line (followed by `\n`) to the program's stdin.
+    pub fn send_line(&mut self, line: &str) -> Result<()> {
+        self.send(format!("{line}\n"))
+    }
+
+    /// Closes the program's stdin, so it sees the end of its input.
+    pub fn close_stdin(&mut self) {
+        self.stdin = None;
+    }
+
+    /// Waits for the program to exit (closing its stdin first), returning its exit status. If it's still running after
+    /// the timeout, it gets killed and an [`ExpectError`] is returned.
+    pub fn wait(&mut self) -> Result<ExitStatus> {
+        self.close_stdin();
+        self.exchange.wait(&mut self.child)
+    }
+
+    /// All the stderr of the program until now.
+    pub fn stderr(&self) -> Vec<u8> {
+        self.stderr.snapshot()
+    }
+
+    /// Everything that was exchanged with the program until now: its stdout and the input sent to it, in order.
+    pub fn transcript(&mut self) -> String {
+        self.exchange.transcript()
+    }
+
+    /// Adds the program's stderr to the transcript of a failed expectation, it may explain why it failed.
+    fn with_stderr(&self, found: Result<String, ExpectError>) -> Result<String> {
+        found.map_err(|mut e| {
+            let stderr = self.stderr();
+            if !stderr.is_empty() {
+                e.transcript.push_str("--- stderr\n");
+                e.transcript.push_str(&String::from_utf8_lossy(&stderr));
+            }
+            e.into()
+        })
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if matches!(self.child.try_wait(), Ok(None)) {
+            kill(&mut self.child);
+        }
+    }
+}
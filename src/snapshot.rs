@@ -0,0 +1,158 @@
+//! Tracking the changes a command makes to the files of a project, see [`Project::snapshot()`](crate::Project::snapshot).
+
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    fmt, fs,
+    hash::Hasher,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+
+use crate::glob;
+
+/// The state of every file in a directory at some point, to be compared with a later one using [`Snapshot::diff()`].
+///
+/// Only files are tracked (directories themselves aren't), with their size, a hash of their contents, their
+/// permissions and their modification time. Symbolic links aren't followed, they're tracked with their target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    files: BTreeMap<PathBuf, FileState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileState {
+    size: u64,
+    hash: u64,
+    /// Unix mode bits, or whether the file is read-only on other platforms.
+    mode: u32,
+    modified: Option<SystemTime>,
+    /// The target, if it's a symbolic link (its size and hash are the ones of the target's path).
+    link: Option<PathBuf>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of all the files inside `dir`, recursively. Their paths are kept relative to `dir`.
+    pub(crate) fn take(dir: &Path) -> Result<Self> {
+        let mut paths = Vec::new();
+        glob::walk(dir, &mut |path| paths.push(path.to_owned()))
+            .with_context(|| format!("Couldn't walk {}", dir.display()))?;
+
+        let mut files = BTreeMap::new();
+        for path in paths {
+            let state = FileState::read(&path)
+                .with_context(|| format!("Couldn't read {}", path.display()))?;
+            let relative = path.strip_prefix(dir).unwrap_or(&path).to_owned();
+            files.insert(relative, state);
+        }
+        Ok(Self { files })
+    }
+
+    /// The paths of all the files in the snapshot, relative to the project's directory.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+
+    /// Lists the changes from this snapshot to a later one (`after`).
+    #[must_use]
+    pub fn diff(&self, after: &Self) -> Changes {
+        let mut changes = Changes::default();
+        for (path, before) in &self.files {
+            match after.files.get(path) {
+                None => changes.deleted.push(path.clone()),
+                Some(after) => {
+                    if before.size != after.size
+                        || before.hash != after.hash
+                        || before.modified != after.modified
+                        || before.link != after.link
+                    {
+                        changes.modified.push(path.clone());
+                    }
+                    if before.mode != after.mode {
+                        changes.permissions_changed.push(path.clone());
+                    }
+                }
+            }
+        }
+        changes.created = after
+            .files
+            .keys()
+            .filter(|path| !self.files.contains_key(*path))
+            .cloned()
+            .collect();
+        changes
+    }
+}
+
+impl FileState {
+    fn read(path: &Path) -> Result<Self> {
+        let metadata = fs::symlink_metadata(path)?;
+        let link = if metadata.file_type().is_symlink() {
+            Some(fs::read_link(path)?)
+        } else {
+            None
+        };
+        let mut hasher = DefaultHasher::new();
+        match &link {
+            Some(target) => hasher.write(target.as_os_str().as_encoded_bytes()),
+            None => hasher.write(&fs::read(path)?),
+        }
+
+        #[cfg(unix)]
+        let mode = std::os::unix::fs::PermissionsExt::mode(&metadata.permissions());
+        #[cfg(not(unix))]
+        let mode = u32::from(metadata.permissions().readonly());
+
+        Ok(Self {
+            size: metadata.len(),
+            hash: hasher.finish(),
+            mode,
+            modified: metadata.modified().ok(),
+            link,
+        })
+    }
+}
+
+/// The files that changed between two [`Snapshot`]s, relative to the project's directory and sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changes {
+    /// Files that didn't exist before.
+    pub created: Vec<PathBuf>,
+    /// Files that don't exist anymore.
+    pub deleted: Vec<PathBuf>,
+    /// Files whose contents or modification time changed.
+    pub modified: Vec<PathBuf>,
+    /// Files whose permissions changed.
+    pub permissions_changed: Vec<PathBuf>,
+}
+
+impl Changes {
+    /// Checks if nothing changed.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.created.is_empty()
+            && self.deleted.is_empty()
+            && self.modified.is_empty()
+            && self.permissions_changed.is_empty()
+    }
+}
+
+impl fmt::Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "No changes");
+        }
+        for (what, paths) in [
+            ("Created", &self.created),
+            ("Deleted", &self.deleted),
+            ("Modified", &self.modified),
+            ("Permissions changed", &self.permissions_changed),
+        ] {
+            for path in paths {
+                writeln!(f, "{what}: {}", path.display())?;
+            }
+        }
+        Ok(())
+    }
+}
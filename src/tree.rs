@@ -0,0 +1,152 @@
+//! Comparing directory trees against an expected tree on disk, see [`Project::check_dir()`](crate::Project::check_dir).
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    bless,
+    data::{self, Substitutions},
+    fixture, glob,
+};
+
+/// Compares the files in `actual` with the files in `expected`, ignoring the ones that match any of the `ignore` globs.
+/// Returns a report of all the differences, if there's any.
+///
+/// If blessing is enabled (see [`bless::enabled()`]), `expected` is updated to match `actual` instead.
+pub(crate) fn compare(
+    actual: &Path,
+    expected: &Path,
+    ignore: &[String],
+    substitutions: Substitutions<'_>,
+) -> Result<Option<String>> {
+    let actual_files = files(actual, ignore)?;
+    let expected_files = if expected.is_dir() || !bless::enabled() {
+        files(expected, ignore)
+            .with_context(|| format!("Couldn't read expected tree {}", expected.display()))?
+    } else {
+        BTreeSet::new()
+    };
+
+    let mut problems = Vec::new();
+    for missing in expected_files.difference(&actual_files) {
+        if bless::enabled() {
+            bless::remove(&expected.join(missing))?;
+        } else {
+            problems.push(format!("Missing file: {missing}"));
+        }
+    }
+    for extra in actual_files.difference(&expected_files) {
+        if bless::enabled() {
+            bless_file(&actual.join(extra), &expected.join(extra), substitutions)?;
+        } else {
+            problems.push(format!("Unexpected file: {extra}"));
+        }
+    }
+    for file in actual_files.intersection(&expected_files) {
+        let (actual_file, expected_file) = (actual.join(file), expected.join(file));
+        let Some(difference) = difference(&actual_file, &expected_file, substitutions)? else {
+            continue;
+        };
+        if bless::enabled() {
+            bless_file(&actual_file, &expected_file, substitutions)?;
+        } else {
+            problems.push(format!("Different contents: {file}\n{difference}"));
+        }
+    }
+
+    Ok((!problems.is_empty()).then(|| problems.join("\n")))
+}
+
+/// Compares two files, returning their differences if they don't match. Text is compared like
+/// [`WithStdout::with_stdout_data()`](crate::WithStdout::with_stdout_data), anything else byte by byte (showing a hex dump).
+/// Symbolic links aren't followed, they match if they have the same target.
+fn difference(
+    actual: &Path,
+    expected: &Path,
+    substitutions: Substitutions<'_>,
+) -> Result<Option<String>> {
+    if fixture::is_link(actual) || fixture::is_link(expected) {
+        let (actual, expected) = (link_target(actual), link_target(expected));
+        return Ok((actual != expected).then(|| {
+            format!(
+                "--- expected\n{}\n--- actual\n{}\n",
+                describe(expected.as_deref()),
+                describe(actual.as_deref())
+            )
+        }));
+    }
+
+    let actual = fs::read(actual)?;
+    let expected = fs::read(expected)?;
+    match (std::str::from_utf8(&actual), std::str::from_utf8(&expected)) {
+        (Ok(actual), Ok(expected)) => Ok(substitutions
+            .compare(expected, actual)
+            .err()
+            .map(|(expected, actual)| data::diff(&expected, &actual))),
+        _ => Ok((actual != expected).then(|| data::hex_diff(&expected, &actual))),
+    }
+}
+
+/// The target of a symbolic link, `None` if `path` isn't one.
+fn link_target(path: &Path) -> Option<PathBuf> {
+    fixture::is_link(path)
+        .then(|| fs::read_link(path).ok())
+        .flatten()
+}
+
+fn describe(link: Option<&Path>) -> String {
+    match link {
+        Some(target) => format!("a link to {}", target.display()),
+        None => "a file".to_owned(),
+    }
+}
+
+/// Updates `expected` to match `actual`: as a link if it's a symbolic link, with its normalized contents if it isn't.
+fn bless_file(actual: &Path, expected: &Path, substitutions: Substitutions<'_>) -> Result<()> {
+    if fixture::is_link(actual) {
+        bless::link(expected, actual)
+    } else {
+        bless::write(expected, contents(actual, substitutions)?)
+    }
+}
+
+/// The contents of a file as they should be blessed: normalized if it's text, as they are if it isn't.
+fn contents(path: &Path, substitutions: Substitutions<'_>) -> Result<Vec<u8>> {
+    let contents = fs::read(path)?;
+    Ok(match String::from_utf8(contents) {
+        Ok(text) => substitutions.normalize(&text).into_bytes(),
+        Err(e) => e.into_bytes(),
+    })
+}
+
+/// Lists the files (and symbolic links) in `dir` (relative to it, with `/` as separator) that aren't ignored.
+///
+/// An `ignore` glob without any `/` is matched against the file names, and against the whole relative path otherwise.
+fn files(dir: &Path, ignore: &[String]) -> Result<BTreeSet<String>> {
+    let mut found = Vec::<PathBuf>::new();
+    glob::walk(dir, &mut |path| found.push(path.to_owned()))?;
+    Ok(found
+        .iter()
+        .map(|path| {
+            path.strip_prefix(dir)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .filter(|relative| {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            !ignore.iter().any(|pattern| {
+                if pattern.contains('/') {
+                    glob::matches(pattern, relative)
+                } else {
+                    glob::matches(pattern, name)
+                }
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,55 @@
+use cli_sandbox::WithStdout;
+use std::{
+    env, fs,
+    process::{ExitStatus, Output},
+};
+
+#[test]
+fn bless_files() {
+    env::set_var("SANDBOX_BLESS", "1");
+    let dir = tempfile::tempdir().unwrap();
+    let output = Output {
+        status: ExitStatus::default(),
+        stdout: b"new stdout\n".to_vec(),
+        stderr: b"new stderr\n".to_vec(),
+    };
+
+    // Missing files get created, outdated ones get updated.
+    let stdout = dir.path().join("snapshots").join("test.stdout");
+    let stderr = dir.path().join("test.stderr");
+    fs::write(&stderr, "old stderr\n").unwrap();
+    output.with_stdout_file(&stdout);
+    output.with_stderr_file(&stderr);
+    assert_eq!(fs::read_to_string(&stdout).unwrap(), "new stdout\n");
+    assert_eq!(fs::read_to_string(&stderr).unwrap(), "new stderr\n");
+
+    // Whole trees get updated too, removing the files that aren't produced anymore.
+    let mut proj = cli_sandbox::project().unwrap();
+    proj.new_file("kept.txt", "new\n").unwrap();
+    let expected = dir.path().join("tree");
+    fs::create_dir(&expected).unwrap();
+    fs::write(expected.join("kept.txt"), "old\n").unwrap();
+    fs::write(expected.join("removed.txt"), "removed\n").unwrap();
+    // Links are blessed as links.
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink("kept.txt", proj.path().join("link.txt")).unwrap();
+        fs::write(expected.join("link.txt"), "not a link\n").unwrap();
+    }
+    proj.check_dir(".", &expected).unwrap();
+    #[cfg(unix)]
+    assert_eq!(
+        fs::read_link(expected.join("link.txt")).unwrap(),
+        std::path::Path::new("kept.txt")
+    );
+    assert_eq!(
+        fs::read_to_string(expected.join("kept.txt")).unwrap(),
+        "new\n"
+    );
+    assert!(!expected.join("removed.txt").exists());
+
+    env::remove_var("SANDBOX_BLESS");
+    output.with_stdout_file(&stdout);
+    output.with_stderr_file(&stderr);
+    proj.check_dir(".", &expected).unwrap();
+}
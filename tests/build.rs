@@ -0,0 +1,96 @@
+#![cfg(unix)]
+
+mod common;
+
+use cli_sandbox::{binary, init, init_build, project, WithStdout};
+use common::PROFILE_DIR;
+use std::{
+    path::{Path, PathBuf},
+    sync::MutexGuard,
+};
+
+/// Where the fixture gets built, shared by all the tests (each binary is only built once per process, wherever it is).
+fn target_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("buildable-target")
+}
+
+/// Moves the process to the buildable fixture.
+fn buildable() -> MutexGuard<'static, ()> {
+    common::process_in("tests/fixtures/buildable", &target_dir())
+}
+
+fn built(dir: &str, profile_dir: &str) -> PathBuf {
+    target_dir().join(
+        Path::new("cli-sandbox")
+            .join(dir)
+            .join(profile_dir)
+            .join("buildable"),
+    )
+}
+
+#[test]
+fn build_on_demand() {
+    let _guard = buildable();
+    init_build([""; 0]);
+    let proj = project().unwrap();
+    let result = proj.command([""; 0]).unwrap();
+    result.with_stdout("hello\n");
+    assert_eq!(result.program(), built("default", PROFILE_DIR));
+    // It's only built once.
+    let again = proj.command([""; 0]).unwrap();
+    assert_eq!(again.program(), result.program());
+}
+
+#[test]
+fn build_features_and_profiles() {
+    let _guard = buildable();
+    init_build(["loud"]);
+    let mut proj = project().unwrap();
+    proj.command([""; 0]).unwrap().with_stdout("HELLO!\n");
+    proj.profile("release");
+    let result = proj.command([""; 0]).unwrap();
+    result.with_stdout("HELLO!\n");
+    assert_eq!(result.program(), built("default+loud", "release"));
+}
+
+#[test]
+fn build_errors() {
+    let _guard = buildable();
+    init_build(["broken"]);
+    let error = format!("{:#}", project().unwrap().command([""; 0]).unwrap_err());
+    assert!(
+        error.starts_with("Couldn't build the binary `buildable`:\nerror: broken on purpose"),
+        "{error}"
+    );
+
+    init_build(["missing"]);
+    let error = format!("{:#}", project().unwrap().command([""; 0]).unwrap_err());
+    assert!(error.contains("missing"), "{error}");
+    // Calling `init()` again stops building on demand.
+    init();
+    let error = format!("{:#}", project().unwrap().command([""; 0]).unwrap_err());
+    assert!(error.starts_with("Couldn't execute "), "{error}");
+}
+
+#[test]
+fn feature_combinations() {
+    let _guard = buildable();
+    init();
+    let mut proj = project().unwrap();
+    proj.binary(binary().features(["loud"]).no_default_features());
+    let result = proj.command([""; 0]).unwrap();
+    result.with_stdout("HEY!\n");
+    assert_eq!(result.program(), built("no-default+loud", PROFILE_DIR));
+    proj.binary(&binary());
+    let result = proj.command([""; 0]).unwrap();
+    result.with_stdout("hello\n");
+    assert_eq!(result.program(), built("default", PROFILE_DIR));
+
+    // Even the ones whose names look the same in a directory name.
+    proj.binary(binary().features(["twice.please"]));
+    proj.command([""; 0]).unwrap().with_stdout("hello\nhello\n");
+    proj.binary(binary().features(["twice_please"]));
+    proj.command([""; 0]).unwrap().with_stdout("hello\n");
+    proj.binary(binary().features(["twice.please"]));
+    proj.command([""; 0]).unwrap().with_stdout("hello\nhello\n");
+}
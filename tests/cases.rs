@@ -0,0 +1,33 @@
+#![cfg(all(unix, feature = "cases"))]
+
+mod common;
+
+#[test]
+fn passing_cases() {
+    common::init();
+    cli_sandbox::cases("tests/cases/*.toml");
+}
+
+#[test]
+#[should_panic(expected = "2 of 2 cases failed")]
+fn failing_cases() {
+    common::init();
+    cli_sandbox::cases("tests/cases-failing/*.toml");
+}
+
+#[test]
+fn linked_directories_not_followed() {
+    common::init();
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("echo.toml"),
+        "args = [\"echo\", \"hi\"]\nstdout = \"bye\"\n",
+    )
+    .unwrap();
+    // Following it would find the same case again inside it, over and over.
+    std::os::unix::fs::symlink(".", dir.path().join("loop")).unwrap();
+    let pattern = format!("{}/**/*.toml", dir.path().display());
+    let error = std::panic::catch_unwind(|| cli_sandbox::cases(&pattern)).unwrap_err();
+    let message = error.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("1 of 1 cases failed"), "{message}");
+}
@@ -0,0 +1,42 @@
+use cli_sandbox::{project, Project};
+
+#[test]
+fn matching_tree() {
+    let proj = Project::from_fixture("tests/fixtures/tree").expect("Couldn't create the project");
+    proj.check_dir(".", "tests/fixtures/tree").unwrap();
+    proj.check_dir("nested", "tests/fixtures/tree/nested")
+        .unwrap();
+}
+
+#[test]
+fn ignored_files() {
+    let mut proj = project().expect("Couldn't create a new project");
+    proj.copy_dir("tests/fixtures/tree", "out").unwrap();
+    proj.new_file("out/debug.log", "ignored").unwrap();
+    proj.new_file("out/nested/trace.log", "ignored").unwrap();
+    proj.check_dir_ignoring("out", "tests/fixtures/tree", ["*.log"])
+        .unwrap();
+    proj.check_dir_ignoring("out", "tests/fixtures/tree", ["*.log", "nested/**"])
+        .unwrap();
+}
+
+#[test]
+#[should_panic = "Missing file: top.txt\nUnexpected file: extra.txt\nDifferent contents: nested/file.txt"]
+fn different_tree() {
+    let mut proj =
+        Project::from_fixture("tests/fixtures/tree").expect("Couldn't create the project");
+    std::fs::remove_file(proj.path().join("top.txt")).unwrap();
+    proj.new_file("extra.txt", "extra\n").unwrap();
+    proj.new_file("nested/file.txt", "changed\n").unwrap();
+    proj.check_dir(".", "tests/fixtures/tree").unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+#[should_panic = "Different contents: link.txt\n--- expected\na link to nested/file.txt\n--- actual\na link to top.txt"]
+fn retargeted_link() {
+    let proj = Project::from_fixture("tests/fixtures/tree").expect("Couldn't create the project");
+    std::fs::remove_file(proj.path().join("link.txt")).unwrap();
+    std::os::unix::fs::symlink("top.txt", proj.path().join("link.txt")).unwrap();
+    proj.check_dir(".", "tests/fixtures/tree").unwrap();
+}
@@ -0,0 +1,281 @@
+#![cfg(unix)]
+
+mod common;
+
+use cli_sandbox::{project, Stdin, TimeoutError, WithStdout};
+use common::init;
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn command_args() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let cmd = proj.command(["echo", "hello", "world"]).unwrap();
+    cmd.with_stdout("hello world\n");
+}
+
+#[test]
+fn builder_env_and_stdin() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    proj.cmd()
+        .args(["env", "SANDBOX_TEST"])
+        .env("SANDBOX_TEST", "value")
+        .run()
+        .unwrap()
+        .with_stdout("value\n");
+
+    proj.cmd()
+        .arg("cat")
+        .stdin("from stdin")
+        .run()
+        .unwrap()
+        .with_stdout("from stdin");
+}
+
+#[test]
+fn builder_current_dir() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    fs::create_dir(proj.path().join("sub")).unwrap();
+    let cmd = proj.cmd().arg("pwd").current_dir("sub").run().unwrap();
+    cmd.with_stdout("[ROOT]/sub\n");
+}
+
+#[test]
+fn timeout_kills() {
+    init();
+    let mut proj = project().expect("Couldn't create a new project");
+    proj.timeout(Duration::from_millis(200));
+    let start = Instant::now();
+    let err = proj.command(["sleep", "10"]).unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    let err = err
+        .downcast::<TimeoutError>()
+        .expect("Error should be a timeout");
+    assert_eq!(err.stdout, b"started\n");
+    assert!(err.to_string().contains("sleep 10"));
+}
+
+#[test]
+fn timeout_background_process() {
+    init();
+    let mut proj = project().expect("Couldn't create a new project");
+    proj.timeout(Duration::from_millis(500));
+    let start = Instant::now();
+    // The program exits right away, but what it leaves running keeps its stdout open.
+    let err = proj.command(["sh", "sleep 10 & echo hi"]).unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    let err = err
+        .downcast::<TimeoutError>()
+        .expect("Error should be a timeout");
+    assert_eq!(err.stdout, b"hi\n");
+}
+
+#[test]
+fn exit_status() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    proj.command(["echo"]).unwrap().success();
+    proj.command(["exit", "3"]).unwrap().failure();
+    proj.command(["exit", "3"]).unwrap().code(3);
+    proj.command(["segfault"]).unwrap().signal(11);
+}
+
+#[test]
+#[should_panic(expected = "exited with code 3\n--- stdout\nout\n\n--- stderr\nerr\n")]
+fn exit_status_report() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    proj.command(["exit", "3"]).unwrap().success();
+}
+
+#[test]
+fn empty_output() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let cmd = proj.command(["echo", "hi"]).unwrap();
+    assert!(!cmd.empty_stdout());
+    assert!(cmd.empty_stderr());
+}
+
+#[test]
+fn command_result_context() {
+    init();
+    let mut proj = project().expect("Couldn't create a new project");
+    proj.env("PROJECT_KEY", "project value");
+    let cmd = proj
+        .cmd()
+        .args(["echo", "two words"])
+        .env("KEY", "value")
+        .run()
+        .unwrap();
+    assert!(cmd
+        .envs()
+        .contains(&("PROJECT_KEY".into(), Some("project value".into()))));
+    assert_eq!(cmd.args(), ["echo", "two words"]);
+    assert_eq!(cmd.current_dir(), proj.path());
+    assert!(cmd.status.success());
+    assert!(cmd.invocation().ends_with(&format!(
+        r#"fake-bin echo "two words" (in {})"#,
+        proj.path().display()
+    )));
+    assert!(cmd
+        .invocation()
+        .starts_with(r#"env -i PROJECT_KEY="project value" KEY=value "#));
+}
+
+#[test]
+#[should_panic(expected = "stdout of `")]
+fn command_result_diff_context() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    proj.command(["echo", "a"]).unwrap().with_stdout("b\n");
+}
+
+#[test]
+fn missing_program() {
+    init();
+    let mut proj = project().expect("Couldn't create a new project");
+    let error = format!(
+        "{:#}",
+        proj.run_program("missing-program", [""; 0]).unwrap_err()
+    );
+    assert!(
+        error.starts_with("Couldn't execute missing-program: "),
+        "{error}"
+    );
+
+    proj.profile("missing-profile");
+    let error = format!("{:#}", proj.command([""; 0]).unwrap_err());
+    assert!(
+        error.contains("missing-profile") && error.contains("may have to be built"),
+        "{error}"
+    );
+    let error = format!("{:#}", proj.cmd().spawn().unwrap_err());
+    assert!(error.starts_with("Couldn't execute "), "{error}");
+}
+
+#[test]
+fn data_root() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    proj.command(["pwd"]).unwrap().with_stdout_data("[ROOT]");
+}
+
+#[test]
+fn redactions() {
+    init();
+    let mut proj = project().expect("Couldn't create a new project");
+    proj.redact("[VERSION]", "1.2.3");
+    let cmd = proj.command(["echo", "v1.2.3"]).unwrap();
+    cmd.with_stdout("v[VERSION]\n");
+
+    proj.command(["pwd"]).unwrap().with_stdout("[ROOT]\n");
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex_redactions() {
+    init();
+    let mut proj = project().expect("Couldn't create a new project");
+    proj.redact_regex("[PID]", r"pid \d+").unwrap();
+    let cmd = proj.command(["echo", "pid 4242"]).unwrap();
+    cmd.with_stdout("[PID]\n");
+}
+
+#[test]
+fn stdin_sources() {
+    init();
+    let mut proj = project().expect("Couldn't create a new project");
+    let result = proj.cmd().arg("cat").stdin(b"\x00bytes\xff").run().unwrap();
+    assert_eq!(result.stdout, b"\x00bytes\xff");
+
+    proj.new_file("input.txt", "from a file\n").unwrap();
+    proj.cmd()
+        .arg("cat")
+        .stdin(Stdin::file("input.txt"))
+        .run()
+        .unwrap()
+        .with_stdout("from a file\n");
+    proj.cmd()
+        .arg("cat")
+        .stdin(Stdin::file("missing.txt"))
+        .run()
+        .unwrap_err();
+
+    proj.cmd()
+        .arg("cat")
+        .stdin(Stdin::Null)
+        .run()
+        .unwrap()
+        .with_stdout("");
+}
+
+#[test]
+fn large_stdin() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    // Much bigger than the pipes' buffers, so `cat` blocks writing its output until we read it.
+    let input = "line\n".repeat(1 << 20);
+    let result = proj.cmd().arg("cat").stdin(input.clone()).run().unwrap();
+    assert!(result.stdout == input.as_bytes());
+}
+
+#[test]
+fn run_program() {
+    let mut proj = project().expect("Couldn't create a new project");
+    proj.run_program("sh", ["-c", "echo created > out.txt; pwd -P"])
+        .unwrap()
+        .with_stdout_data("[ROOT]\n");
+    proj.check_file("out.txt", "created\n").unwrap();
+
+    proj.new_file("script.sh", "echo \"script $1 $SANDBOX_TEST\"\n")
+        .unwrap();
+    proj.program("sh")
+        .args(["script.sh", "arg"])
+        .env("SANDBOX_TEST", "value")
+        .run()
+        .unwrap()
+        .with_stdout("script arg value\n");
+
+    let result = proj.run_program("false", [""; 0]).unwrap();
+    assert_eq!(result.program(), std::path::Path::new("false"));
+    result.failure();
+}
+
+#[test]
+fn project_env() {
+    init();
+    let mut proj = project().expect("Couldn't create a new project");
+    // Cargo sets it for the tests, but the programs don't inherit it.
+    proj.command(["env", "CARGO_MANIFEST_DIR"])
+        .unwrap()
+        .with_stdout("");
+    proj.env("SANDBOX_GREETING", "hi")
+        .env("SANDBOX_OTHER", "other")
+        .env_inherit("CARGO_MANIFEST_DIR");
+    proj.command(["env", "SANDBOX_GREETING"])
+        .unwrap()
+        .with_stdout("hi\n");
+    proj.command(["env", "CARGO_MANIFEST_DIR"])
+        .unwrap()
+        .with_stdout(format!("{}\n", env!("CARGO_MANIFEST_DIR")));
+    proj.cmd()
+        .args(["env", "SANDBOX_GREETING"])
+        .env_clear()
+        .run()
+        .unwrap()
+        .with_stdout("");
+
+    proj.clean_env("SANDBOX_");
+    proj.command(["env", "SANDBOX_OTHER"])
+        .unwrap()
+        .with_stdout("");
+    assert!(std::env::var_os("SANDBOX_TARGET_DIR").is_some());
+}
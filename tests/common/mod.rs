@@ -0,0 +1,81 @@
+// Each test crate only uses some of these.
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, OnceLock, PoisonError},
+};
+
+/// Whether the binary under test is looked for in the `release` profile when none is chosen, as it is with only the
+/// `release` feature enabled.
+pub const RELEASE_BY_DEFAULT: bool = cfg!(all(feature = "release", not(feature = "dev")));
+
+/// The directory (inside the target directory) of the profile used when none is chosen.
+pub const PROFILE_DIR: &str = if RELEASE_BY_DEFAULT {
+    "release"
+} else {
+    "debug"
+};
+
+/// A shell script standing in for the binary under test, as this crate doesn't have one.
+const FAKE_BIN: &str = r#"#!/bin/sh
+case "$1" in
+    echo) shift; echo "$@" ;;
+    cat) cat ;;
+    env) printenv "$2" ;;
+    pwd) pwd -P ;;
+    sleep) echo started; sleep "$2" ;;
+    exit) echo out; echo err >&2; exit "$2" ;;
+    segfault) kill -SEGV $$ ;;
+    read) cat "$2" ;;
+    sh) sh -c "$2" ;;
+    prompt) printf 'Name? '; read -r name; echo "Hello, $name!" ;;
+    repl) while printf 'prompt> ' && read -r line; do
+        case "$line" in
+            exit) exit 3 ;;
+            *) echo "got $line"; echo "warning: $line" >&2 ;;
+        esac
+    done ;;
+esac
+"#;
+
+pub fn init() {
+    static TARGET_DIR: OnceLock<PathBuf> = OnceLock::new();
+    let target_dir = TARGET_DIR.get_or_init(|| {
+        let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("fake-target");
+        fs::create_dir_all(dir.join(PROFILE_DIR)).expect("Couldn't create the fake target dir");
+        // Other test binaries may be using the fake binary, so replace it atomically.
+        let tmp = dir.join(format!("fake-bin.{}", std::process::id()));
+        fs::write(&tmp, FAKE_BIN).expect("Couldn't write the fake binary");
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755))
+            .expect("Couldn't make the fake binary executable");
+        fs::rename(&tmp, dir.join(PROFILE_DIR).join("fake-bin"))
+            .expect("Couldn't move the fake binary");
+        dir
+    });
+    env::set_var("SANDBOX_TARGET_DIR", target_dir);
+    env::set_var("SANDBOX_PKG_NAME", "fake-bin");
+}
+
+/// Moves the whole process to the package in `dir` (relative to this package's root), building in `target_dir`
+/// (`CARGO_TARGET_DIR`) and without the binaries and metadata left by other tests. The tests that call it run one at a
+/// time, as long as they keep the returned guard.
+pub fn process_in(dir: &str, target_dir: &Path) -> MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    // A failing test poisons the lock, that doesn't affect the others.
+    let guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    for (key, _) in env::vars_os() {
+        if key
+            .to_str()
+            .is_some_and(|key| key.starts_with("SANDBOX_") || key.starts_with("CARGO_BIN_EXE_"))
+        {
+            env::remove_var(key);
+        }
+    }
+    env::set_var("CARGO_TARGET_DIR", target_dir);
+    env::set_current_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join(dir))
+        .expect("Couldn't change the current directory");
+    guard
+}
@@ -0,0 +1,58 @@
+use cli_sandbox::WithStdout;
+use std::process::{ExitStatus, Output};
+
+fn output(stdout: &str) -> Output {
+    Output {
+        status: ExitStatus::default(),
+        stdout: stdout.into(),
+        stderr: Vec::new(),
+    }
+}
+
+#[test]
+fn exact_data() {
+    output("hello\nworld\n").with_stdout_data("hello\nworld");
+    output("hello\n\n").with_stdout_data("hello");
+    output("hello").with_stdout_data("hello\n\n");
+}
+
+#[test]
+fn wildcard_in_line() {
+    let out = output("Compiling foo v0.1.0 (/some/path)\nFinished in 0.32s\n");
+    out.with_stdout_data("Compiling foo [..] ([..])\nFinished in [..]s\n");
+    out.with_stdout_data("[..]\n[..]");
+}
+
+#[test]
+fn wildcard_lines() {
+    let out = output("first\nnoise\nmore noise\nlast\n");
+    out.with_stdout_data("first\n...\nlast\n");
+    out.with_stdout_data("...\nlast\n");
+    out.with_stdout_data("first\nnoise\nmore noise\n...\nlast\n");
+}
+
+#[test]
+fn exe_suffix() {
+    output(&format!("built foo{}\n", std::env::consts::EXE_SUFFIX))
+        .with_stdout_data("built foo[EXE]");
+}
+
+#[test]
+#[should_panic]
+fn wildcard_doesnt_span_lines() {
+    output("one\ntwo\n").with_stdout_data("one[..]two");
+}
+
+#[test]
+#[should_panic]
+fn missing_lines() {
+    output("first\nlast\n").with_stdout_data("first\n...\nmiddle\nlast");
+}
+
+#[test]
+fn many_lines() {
+    let out = output(&"line\n".repeat(20_000));
+    out.with_stdout_data("line\n".repeat(20_000));
+    out.with_stdout_data("line\n...\nline\n");
+    out.with_stdout_data("...\n");
+}
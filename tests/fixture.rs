@@ -0,0 +1,64 @@
+use cli_sandbox::{project, Project};
+use std::fs;
+
+#[test]
+fn from_fixture() {
+    let proj = Project::from_fixture("tests/fixtures/tree").expect("Couldn't create the project");
+    proj.check_file("top.txt", "top\n").unwrap();
+    proj.check_file("nested/file.txt", "nested\n").unwrap();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let script = proj.path().join("nested/deeper/script.sh");
+        let mode = fs::metadata(script).unwrap().permissions().mode();
+        assert_eq!(mode & 0o111, 0o111, "Permissions weren't kept");
+
+        let link = proj.path().join("link.txt");
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(
+            fs::read_link(&link).unwrap().to_str(),
+            Some("nested/file.txt")
+        );
+        proj.check_file("link.txt", "nested\n").unwrap();
+    }
+}
+
+#[test]
+fn copy_dir() {
+    let proj = project().expect("Couldn't create a new project");
+    proj.copy_dir("tests/fixtures/tree/nested", "a/b").unwrap();
+    proj.check_file("a/b/file.txt", "nested\n").unwrap();
+    assert!(proj.path().join("a/b/deeper/script.sh").is_file());
+}
+
+#[test]
+fn copy_dir_again() {
+    let mut proj = project().expect("Couldn't create a new project");
+    proj.copy_dir("tests/fixtures/tree", "out").unwrap();
+    proj.new_file("out/top.txt", "changed\n").unwrap();
+    // Links are replaced too, like files.
+    proj.copy_dir("tests/fixtures/tree", "out").unwrap();
+    proj.check_file("out/top.txt", "top\n").unwrap();
+    proj.check_file("out/link.txt", "nested\n").unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn copy_dir_over_links() {
+    let outside = tempfile::tempdir().unwrap();
+    let target = outside.path().join("outside.txt");
+    fs::write(&target, "outside\n").unwrap();
+
+    let proj = project().expect("Couldn't create a new project");
+    fs::create_dir(proj.path().join("out")).unwrap();
+    std::os::unix::fs::symlink(&target, proj.path().join("out/top.txt")).unwrap();
+    proj.copy_dir("tests/fixtures/tree", "out").unwrap();
+
+    // The link is replaced, not followed.
+    assert!(!fs::symlink_metadata(proj.path().join("out/top.txt"))
+        .unwrap()
+        .is_symlink());
+    proj.check_file("out/top.txt", "top\n").unwrap();
+    assert_eq!(fs::read_to_string(&target).unwrap(), "outside\n");
+}
@@ -0,0 +1,18 @@
+#[cfg(feature = "broken")]
+compile_error!("broken on purpose");
+
+fn main() {
+    let greeting = if cfg!(feature = "polite") {
+        "hello"
+    } else {
+        "hey"
+    };
+    let times = if cfg!(feature = "twice.please") { 2 } else { 1 };
+    for _ in 0..times {
+        if cfg!(feature = "loud") {
+            println!("{}!", greeting.to_uppercase());
+        } else {
+            println!("{greeting}");
+        }
+    }
+}
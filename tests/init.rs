@@ -0,0 +1,144 @@
+#![cfg(unix)]
+
+mod common;
+
+use cli_sandbox::{init, init_with, project, WithStdout};
+use common::{PROFILE_DIR, RELEASE_BY_DEFAULT};
+use std::{env, fs, os::unix::fs::PermissionsExt, panic, path::Path, sync::MutexGuard};
+use tempfile::TempDir;
+
+/// Creates a fake binary that prints its name and profile, where Cargo would have built it.
+fn fake_bin(target_dir: &Path, profile_dir: &str, name: &str) {
+    let path = target_dir.join(profile_dir).join(name);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, format!("#!/bin/sh\necho {name} {profile_dir}\n")).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// Moves the process to `dir` inside the workspace fixture, with all its binaries built in the default profile (in
+/// the returned target directory).
+fn workspace(dir: &str) -> (TempDir, MutexGuard<'static, ()>) {
+    let target_dir = tempfile::tempdir().unwrap();
+    let guard = common::process_in(
+        &format!("tests/fixtures/workspace/{dir}"),
+        target_dir.path(),
+    );
+    for bin in ["app-server", "app-client", "tool"] {
+        fake_bin(target_dir.path(), PROFILE_DIR, bin);
+    }
+    (target_dir, guard)
+}
+
+#[test]
+fn virtual_workspace() {
+    let _process = workspace("");
+    // A virtual workspace with several binaries doesn't have a default one.
+    init();
+    let mut proj = project().unwrap();
+    let error = proj.command([""; 0]).unwrap_err().to_string();
+    assert!(
+        error.starts_with("There isn't a default binary to test")
+            && error.ends_with("the available ones are: app-client, app-server, tool"),
+        "{error}"
+    );
+    proj.bin("tool").unwrap();
+    proj.command([""; 0])
+        .unwrap()
+        .with_stdout(format!("tool {PROFILE_DIR}\n"));
+    let error = proj.bin("missing").unwrap_err().to_string();
+    assert_eq!(
+        error,
+        "There's no binary named `missing`, the available ones are: app-client, app-server, tool"
+    );
+}
+
+#[test]
+fn chosen_bin() {
+    let _process = workspace("");
+    init_with("app", "app-server");
+    let proj = project().unwrap();
+    proj.command([""; 0])
+        .unwrap()
+        .with_stdout(format!("app-server {PROFILE_DIR}\n"));
+
+    let error = panic::catch_unwind(|| init_with("app", "tool")).unwrap_err();
+    assert_eq!(
+        error.downcast_ref::<String>().unwrap(),
+        "The package `app` doesn't have a binary named `tool`, the available ones are: app-client, app-server"
+    );
+}
+
+#[test]
+fn single_bin() {
+    let _process = workspace("tool");
+    // A package with a single binary uses it, whatever its name.
+    init();
+    project()
+        .unwrap()
+        .command([""; 0])
+        .unwrap()
+        .with_stdout(format!("tool {PROFILE_DIR}\n"));
+}
+
+#[test]
+fn runtime_profiles_and_targets() {
+    let (target_dir, _guard) = workspace("tool");
+    init();
+    fake_bin(target_dir.path(), "release", "tool");
+    fake_bin(target_dir.path(), "ci", "tool");
+    let mut proj = project().unwrap();
+    proj.profile("release");
+    proj.command([""; 0]).unwrap().with_stdout("tool release\n");
+    proj.cmd()
+        .profile("ci")
+        .run()
+        .unwrap()
+        .with_stdout("tool ci\n");
+
+    fake_bin(
+        target_dir.path(),
+        "x86_64-unknown-linux-musl/release",
+        "tool",
+    );
+    proj.target("x86_64-unknown-linux-musl");
+    proj.command([""; 0])
+        .unwrap()
+        .with_stdout("tool x86_64-unknown-linux-musl/release\n");
+}
+
+#[test]
+fn cargo_bins() {
+    let (target_dir, _guard) = workspace("tool");
+    init();
+    fake_bin(target_dir.path(), "release", "tool");
+    let mut proj = project().unwrap();
+    proj.profile("release");
+
+    // The binaries Cargo built for the tests are preferred, unless a profile is chosen (or `release` is the default).
+    let elsewhere = tempfile::tempdir().unwrap();
+    fake_bin(elsewhere.path(), "debug", "tool");
+    env::set_var("CARGO_BIN_EXE_tool", elsewhere.path().join("debug/tool"));
+    let result = project().unwrap().command([""; 0]).unwrap();
+    if RELEASE_BY_DEFAULT {
+        assert_eq!(result.program(), target_dir.path().join("release/tool"));
+    } else {
+        assert!(result.program().starts_with(elsewhere.path()));
+    }
+    proj.command([""; 0]).unwrap().with_stdout("tool release\n");
+
+    // And they're found even without `init()`, if there's only one.
+    env::remove_var("SANDBOX_TARGET_DIR");
+    env::remove_var("SANDBOX_PKG_NAME");
+    let result = project().unwrap().command([""; 0]);
+    if RELEASE_BY_DEFAULT {
+        result.unwrap_err();
+    } else {
+        assert!(result.unwrap().program().starts_with(elsewhere.path()));
+    }
+    env::set_var("CARGO_BIN_EXE_other", elsewhere.path().join("debug/other"));
+    let error = project().unwrap().command([""; 0]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Couldn't find your binary, call `cli_sandbox::init()` first"
+    );
+}
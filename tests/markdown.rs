@@ -0,0 +1,25 @@
+#![cfg(unix)]
+
+mod common;
+
+use cli_sandbox::check_markdown;
+
+#[test]
+fn transcripts() {
+    common::init();
+    check_markdown("tests/fixtures/transcripts.md");
+}
+
+#[test]
+#[should_panic(expected = "1 of 2 commands failed")]
+fn failing_transcripts() {
+    common::init();
+    check_markdown("tests/fixtures/transcripts-failing.md");
+}
+
+#[test]
+#[should_panic(expected = "1 of 1 commands failed")]
+fn nested_fences() {
+    common::init();
+    check_markdown("tests/fixtures/transcripts-nested.md");
+}
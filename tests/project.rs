@@ -1,4 +1,5 @@
 use cli_sandbox::project;
+#[cfg(feature = "pretty_assertions")]
 use pretty_assertions::assert_ne;
 
 #[test]
@@ -15,3 +16,42 @@ fn multiple_projects_different_paths() {
 
     assert_ne!(proj1.path(), proj2.path());
 }
+
+#[test]
+fn binary_files() {
+    let mut proj = project().expect("Couldn't create a new project");
+    let png = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0xff, 0x00];
+    proj.new_file("image.png", png).unwrap();
+    proj.check_file("image.png", png).unwrap();
+    proj.new_file("text.txt", b"text\n").unwrap();
+    proj.check_file("text.txt", "text\n").unwrap();
+}
+
+#[test]
+#[should_panic = "First difference at offset 0x12 (expected 20 bytes, got 20 bytes)"]
+fn binary_files_differ() {
+    let mut proj = project().expect("Couldn't create a new project");
+    let mut contents = vec![0xff; 20];
+    proj.new_file("data.bin", &contents).unwrap();
+    contents[0x12] = 0;
+    proj.check_file("data.bin", &contents).unwrap();
+}
+
+#[test]
+fn nested_files() {
+    let mut proj = project().expect("Couldn't create a new project");
+    proj.new_file("src/nested/a.txt", "a").unwrap();
+    proj.check_file("src/nested/a.txt", "a").unwrap();
+
+    proj.mkdir_all("empty/dir").unwrap();
+    proj.mkdir_all("empty/dir").unwrap();
+    assert!(proj.path().join("empty/dir").is_dir());
+
+    proj.new_files([
+        ("Cargo.toml", "[package]"),
+        ("src/bin/b.rs", "fn main() {}"),
+    ])
+    .unwrap();
+    proj.check_file("Cargo.toml", "[package]").unwrap();
+    proj.check_file("src/bin/b.rs", "fn main() {}").unwrap();
+}
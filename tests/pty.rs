@@ -0,0 +1,105 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use cli_sandbox::{project, ExpectError, Key};
+use common::init;
+use std::time::Duration;
+
+#[test]
+fn prompt() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let mut session = proj.cmd().arg("prompt").spawn_pty().unwrap();
+    session.expect("Name? ").unwrap();
+    session.send_line("Ferris").unwrap();
+    session.expect("Hello, Ferris!").unwrap();
+    session.expect_eof().unwrap();
+    assert!(session.wait().unwrap().success());
+}
+
+#[test]
+fn terminal() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let mut session = proj
+        .cmd()
+        .args([
+            "sh",
+            "[ -t 0 ] && [ -t 1 ] && echo \"tty $TERM\"; stty size; read -r _; stty size",
+        ])
+        .spawn_pty()
+        .unwrap();
+    session.expect("tty xterm-256color").unwrap();
+    session.expect("24 80").unwrap();
+    session.resize(40, 120).unwrap();
+    session.send_key(Key::Enter).unwrap();
+    session.expect("40 120").unwrap();
+    assert!(session.wait().unwrap().success());
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let mut session = proj.cmd().args(["echo", "took 42ms"]).spawn_pty().unwrap();
+    let output = session.expect_regex(r"took \d+ms").unwrap();
+    assert!(output.ends_with("took 42ms"), "{output:?}");
+}
+
+#[test]
+fn expect_timeout() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let mut session = proj.cmd().arg("prompt").spawn_pty().unwrap();
+    let error = session
+        .timeout(Duration::from_millis(200))
+        .expect("never")
+        .unwrap_err()
+        .downcast::<ExpectError>()
+        .unwrap();
+    assert!(!error.eof);
+    assert_eq!(error.transcript, "--- output\nName? \n");
+    assert!(
+        error.to_string().starts_with("Timed out after 0.2"),
+        "{error}"
+    );
+    let error = session
+        .expect_timeout("never", Duration::from_millis(100))
+        .unwrap_err()
+        .downcast::<ExpectError>()
+        .unwrap();
+    assert!(
+        error.to_string().starts_with("Timed out after 0.1"),
+        "{error}"
+    );
+
+    session.send_key(Key::CtrlC).unwrap();
+    assert!(!session.wait().unwrap().success());
+}
+
+#[test]
+fn per_expectation_timeouts() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let mut session = proj.cmd().arg("prompt").spawn_pty().unwrap();
+    #[cfg(feature = "regex")]
+    {
+        let error = session
+            .expect_regex_timeout(r"never \d+", Duration::from_millis(200))
+            .unwrap_err()
+            .downcast::<ExpectError>()
+            .unwrap();
+        assert!(!error.eof);
+    }
+    let error = session
+        .expect_eof_timeout(Duration::from_millis(200))
+        .unwrap_err()
+        .downcast::<ExpectError>()
+        .unwrap();
+    assert!(!error.eof);
+
+    session.send_line("Ferris").unwrap();
+    session.expect_eof_timeout(Duration::from_secs(5)).unwrap();
+}
@@ -0,0 +1,133 @@
+#![cfg(unix)]
+
+mod common;
+
+use cli_sandbox::{project, ExpectError};
+use common::init;
+use std::time::{Duration, Instant};
+
+#[test]
+fn repl() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let mut session = proj.cmd().arg("repl").spawn().unwrap();
+    session.expect("prompt> ").unwrap();
+    session.send_line("help").unwrap();
+    assert_eq!(session.expect("prompt> ").unwrap(), "got help\nprompt> ");
+    session.send_line("exit").unwrap();
+    assert_eq!(session.expect_eof().unwrap(), "");
+    assert_eq!(session.wait().unwrap().code(), Some(3));
+    assert_eq!(session.stderr(), b"warning: help\n");
+    assert_eq!(
+        session.transcript(),
+        "--- output\nprompt> \n--- input\nhelp\n--- output\ngot help\nprompt> \n--- input\nexit\n"
+    );
+}
+
+#[test]
+fn end_of_input() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let mut session = proj.cmd().arg("repl").spawn().unwrap();
+    session.close_stdin();
+    session.expect_eof().unwrap();
+    assert!(session.wait().unwrap().success());
+    session.send_line("too late").unwrap_err();
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let mut session = proj.cmd().arg("repl").spawn().unwrap();
+    session.send_line("42").unwrap();
+    assert_eq!(session.expect_regex(r"got \d+").unwrap(), "prompt> got 42");
+}
+
+#[test]
+fn expect_failures() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let mut session = proj.cmd().arg("repl").spawn().unwrap();
+    session.expect("prompt> ").unwrap();
+    session.send_line("first").unwrap();
+    let error = session
+        .expect_timeout("never", Duration::from_millis(200))
+        .unwrap_err()
+        .downcast::<ExpectError>()
+        .unwrap();
+    assert!(!error.eof);
+    assert_eq!(
+        error.transcript,
+        "--- output\nprompt> \n--- input\nfirst\n--- output\ngot first\nprompt> \n--- stderr\nwarning: first\n"
+    );
+
+    session.send_line("exit").unwrap();
+    let error = session
+        .expect("never")
+        .unwrap_err()
+        .downcast::<ExpectError>()
+        .unwrap();
+    assert!(error.eof);
+    assert!(error
+        .to_string()
+        .starts_with("The program's output ended while waiting for `never`"));
+}
+
+#[test]
+fn killed_on_timeout() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let mut session = proj.cmd().args(["sleep", "10"]).spawn().unwrap();
+    session.expect("started").unwrap();
+    session.timeout(Duration::from_millis(200));
+    let error = session
+        .wait()
+        .unwrap_err()
+        .downcast::<ExpectError>()
+        .unwrap();
+    assert_eq!(error.expected, "the program to exit");
+}
+
+#[test]
+fn invalid_utf8() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    // An invalid byte, then a character that is split between two writes.
+    let mut session = proj
+        .cmd()
+        .args(["sh", r"printf 'a\377b\342\202'; sleep 0.2; printf '\254c'"])
+        .spawn()
+        .unwrap();
+    assert_eq!(session.expect("b").unwrap(), "a\u{FFFD}b");
+    assert_eq!(session.expect("c").unwrap(), "€c");
+    assert_eq!(session.expect_eof().unwrap(), "");
+}
+
+#[test]
+fn per_expectation_timeouts() {
+    init();
+    let proj = project().expect("Couldn't create a new project");
+    let mut session = proj.cmd().arg("repl").spawn().unwrap();
+    let start = Instant::now();
+    #[cfg(feature = "regex")]
+    {
+        let error = session
+            .expect_regex_timeout(r"never \d+", Duration::from_millis(200))
+            .unwrap_err()
+            .downcast::<ExpectError>()
+            .unwrap();
+        assert!(!error.eof);
+    }
+    let error = session
+        .expect_eof_timeout(Duration::from_millis(200))
+        .unwrap_err()
+        .downcast::<ExpectError>()
+        .unwrap();
+    assert!(!error.eof);
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    session.send_line("exit").unwrap();
+    session.expect_eof_timeout(Duration::from_secs(5)).unwrap();
+}
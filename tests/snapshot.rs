@@ -0,0 +1,74 @@
+#![cfg(unix)]
+
+mod common;
+
+use cli_sandbox::project;
+use common::init;
+use std::path::PathBuf;
+
+#[test]
+fn command_tracked() {
+    init();
+    let mut proj = project().expect("Couldn't create a new project");
+    proj.new_files([
+        ("kept.txt", "kept"),
+        ("modified.txt", "before"),
+        ("deleted.txt", "deleted"),
+        ("script.sh", "echo"),
+    ])
+    .unwrap();
+
+    let (result, changes) = proj
+        .command_tracked([
+            "sh",
+            "echo after > modified.txt && rm deleted.txt && mkdir out && echo > out/created.txt && chmod +x script.sh",
+        ])
+        .unwrap();
+    assert!(result.status.success());
+    assert_eq!(changes.created, [PathBuf::from("out/created.txt")]);
+    assert_eq!(changes.deleted, [PathBuf::from("deleted.txt")]);
+    assert_eq!(changes.modified, [PathBuf::from("modified.txt")]);
+    assert_eq!(changes.permissions_changed, [PathBuf::from("script.sh")]);
+    assert_eq!(
+        changes.to_string(),
+        "Created: out/created.txt\nDeleted: deleted.txt\nModified: modified.txt\nPermissions changed: script.sh\n"
+    );
+}
+
+#[test]
+fn no_changes() {
+    init();
+    let mut proj = project().expect("Couldn't create a new project");
+    proj.new_file("file.txt", "contents").unwrap();
+    let before = proj.snapshot().unwrap();
+    proj.command(["read", "file.txt"]).unwrap();
+    let changes = before.diff(&proj.snapshot().unwrap());
+    assert!(changes.is_empty(), "{changes}");
+    assert_eq!(
+        before.paths().collect::<Vec<_>>(),
+        [PathBuf::from("file.txt")]
+    );
+}
+
+#[test]
+fn links_not_followed() {
+    init();
+    let mut proj = project().expect("Couldn't create a new project");
+    proj.new_file("real/file.txt", "contents").unwrap();
+    proj.command(["sh", "ln -s real dir-link && ln -s missing dangling"])
+        .unwrap();
+    let before = proj.snapshot().unwrap();
+    assert_eq!(
+        before.paths().collect::<Vec<_>>(),
+        [
+            PathBuf::from("dangling"),
+            PathBuf::from("dir-link"),
+            PathBuf::from("real/file.txt")
+        ]
+    );
+
+    proj.command(["sh", "ln -sfn real/file.txt dangling"])
+        .unwrap();
+    let changes = before.diff(&proj.snapshot().unwrap());
+    assert_eq!(changes.to_string(), "Modified: dangling\n");
+}
@@ -8,10 +8,10 @@ use std::{env, fs, str};
 #[test]
 fn version() {
     Settings::new().verbosity(Verbosity::Minimal).install();
-    let bless = match env::var("BLESS").unwrap_or(String::from("false")).as_str() {
-        "true" => true,
-        _ => false,
-    };
+    let bless = matches!(
+        env::var("BLESS").unwrap_or(String::from("false")).as_str(),
+        "true"
+    );
     let repo = Repository::open(".").expect("Couldn't open git repository");
 
     let mut hash = Blake2s256::new();